@@ -0,0 +1,60 @@
+use std::fs::File;
+use std::io::Write;
+use std::process::exit;
+use ray_tracer_challenge::*;
+
+fn usage() -> ! {
+    eprintln!("usage: imgdiff <expected.ppm> <actual.ppm> [--tolerance <rmse>] [--diff <heat-map.ppm>]");
+    exit(2);
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let mut files = vec![];
+    let mut tolerance = 0.0f32;
+    let mut diff_path = None;
+
+    let mut i = 0;
+    while i < args.len() {
+        match args[i].as_str() {
+            | "--tolerance" => {
+                i += 1;
+                tolerance = args.get(i)
+                    .and_then(|t| t.parse().ok())
+                    .filter(|t: &f32| t.is_finite() && *t >= 0.0)
+                    .unwrap_or_else(|| usage());
+            }
+            | "--diff" => {
+                i += 1;
+                diff_path = Some(args.get(i).cloned().unwrap_or_else(|| usage()));
+            }
+            | file => files.push(file.to_string()),
+        }
+        i += 1;
+    }
+    if files.len() != 2 {
+        usage();
+    }
+
+    let diff = load_ppm(&files[0])
+        .and_then(|a| load_ppm(&files[1]).map(|b| (a, b)))
+        .and_then(|(a, b)| compare_images(&a, &b))
+        .unwrap_or_else(|e| {
+            eprintln!("imgdiff: {e}");
+            exit(2);
+        });
+
+    println!("rmse {:.6} psnr {:.2} dB max {:.6}", diff.rmse, diff.psnr, diff.max_diff);
+
+    if let Some(path) = diff_path {
+        let written = File::create(&path).and_then(|mut f| f.write_all(canvas_to_ppm(diff.heat_map).as_bytes()));
+        if let Err(e) = written {
+            eprintln!("imgdiff: {path}: {e}");
+            exit(2);
+        }
+    }
+
+    if diff.rmse > tolerance {
+        exit(1);
+    }
+}
@@ -1,8 +1,16 @@
 use std::any::Any;
+use std::cell::{Cell, RefCell};
 use std::cmp::Ordering;
 use std::fmt::{Debug, Formatter};
+use std::fs::File;
+use std::io::Write;
 use std::mem::swap;
-use std::ops::{Add, Div, Mul, Neg, Sub};
+use std::ops::{Add, Div, Mul, Neg, Range, Sub};
+use std::sync::atomic::{AtomicBool, AtomicUsize};
+use std::sync::atomic::Ordering::Relaxed;
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
 use std::vec;
 use uuid::Uuid;
 
@@ -10,36 +18,76 @@ pub const EPS: f32 = 0.0001;
 
 pub const DEFAULT_REFLECTION_NUMBER :u8 = 4;
 
-pub trait Shape {
+pub const DEFAULT_PATH_DEPTH :u8 = 8;
+/// Red, green and blue wavelengths in micrometres
+pub const RGB_WAVELENGTHS: [f32; 3] = [0.65, 0.55, 0.45];
+
+thread_local! {
+    static RANDOM_STATE: Cell<u64> = const { Cell::new(0x853c_49e6_748f_ea9b) };
+    static RECORDING: Cell<bool> = const { Cell::new(false) };
+    static RAY_RECORDER: RefCell<RayRecorder> = const { RefCell::new(RayRecorder { open: vec![], roots: vec![] }) };
+}
+
+pub trait Shape: Send + Sync {
     fn id(&self) -> Uuid;
     fn transform(&self) -> Matrix;
     fn material(&self) -> &Material;
     fn mut_material(&mut self) -> &mut Material;
     fn set_transform(&mut self, transform: Matrix);
     fn set_material(&mut self, material: Material);
+    fn end_transform(&self) -> Option<Matrix>;
+    fn set_end_transform(&mut self, transform: Option<Matrix>);
+    fn visibility(&self) -> Visibility;
+    fn set_visibility(&mut self, visibility: Visibility);
 
-    fn local_intersect(&self, ray: Ray) -> Vec<Intersection>;
+    fn local_intersect(&self, ray: Ray) -> Vec<Intersection<'_>>;
 
     fn local_normal_at(&self, point: Point) -> Vector;
 
-    fn intersect(&self, ray: Ray) -> Vec<Intersection> {
-        let local_ray = transform(ray, inverse(&self.transform()));
+    fn transform_at(&self, time: f32) -> Matrix {
+        match self.end_transform() {
+            | None => self.transform(),
+            | Some(end) => interpolate_transform(&self.transform(), &end, time)
+        }
+    }
+
+    fn intersect(&self, ray: Ray) -> Vec<Intersection<'_>> {
+        let local_ray = transform(ray, inverse(&self.transform_at(ray.time)));
         self.local_intersect(local_ray)
     }
 
     fn normal_at(&self, point: Point) -> Vector {
-        let local_point = inverse(&self.transform()) * point;
+        self.normal_at_time(point, 0.0)
+    }
+
+    fn normal_at_time(&self, point: Point, time: f32) -> Vector {
+        let transform = self.transform_at(time);
+        let local_point = inverse(&transform) * point;
         let local_normal = self.local_normal_at(local_point);
-        let mut world_normal = transpose(inverse(&self.transform())) * local_normal;
+        let mut world_normal = transpose(inverse(&transform)) * local_normal;
         world_normal.w = 0.0;
 
         normalize(world_normal)
     }
 
     fn as_any(&self) -> &dyn Any;
+
+    fn is_volume(&self) -> bool {
+        false
+    }
+
+    fn casts_shadow(&self) -> bool {
+        self.visibility().shadow
+    }
+
+    fn set_casts_shadow(&mut self, casts_shadow: bool) {
+        let mut visibility = self.visibility();
+        visibility.shadow = casts_shadow;
+        self.set_visibility(visibility);
+    }
 }
 
-pub trait Pattern {
+pub trait Pattern: Send + Sync {
     fn set_transform(&mut self, transform: Matrix);
 
     fn transform(&self) -> Matrix;
@@ -48,7 +96,12 @@ pub trait Pattern {
 
     fn pattern_at_shape(&self, object: &dyn Shape, point: Point) -> Color
     {
-        let object_point = inverse(&object.transform()) * point;
+        self.pattern_at_shape_time(object, point, 0.0)
+    }
+
+    fn pattern_at_shape_time(&self, object: &dyn Shape, point: Point, time: f32) -> Color
+    {
+        let object_point = inverse(&object.transform_at(time)) * point;
         let pattern_point = inverse(&self.transform()) * object_point;
         self.pattern_at(pattern_point)
     }
@@ -69,6 +122,7 @@ pub struct Color {
     pub blue: f32
 }
 
+#[derive(Debug, Clone)]
 pub struct Canvas {
     pub width: i32,
     pub height: i32,
@@ -84,13 +138,47 @@ pub struct Matrix {
 #[derive(Debug, Copy, Clone)]
 pub struct Ray {
     pub origin: Point,
-    pub direction: Vector
+    pub direction: Vector,
+    /// Time within the frame, shapes move from their transform at 0 to their end transform at 1
+    pub time: f32,
+    pub kind: RayKind,
+    /// The single RGB channel a ray split by dispersion carries, None for white light
+    pub channel: Option<usize>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RayKind {
+    Camera,
+    Shadow,
+    Reflection,
+    Refraction,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Visibility {
+    pub camera: bool,
+    pub shadow: bool,
+    pub reflection: bool,
+    pub refraction: bool,
+}
+
+impl Visibility {
+    pub fn sees(&self, kind: RayKind) -> bool {
+        match kind {
+            | RayKind::Camera => self.camera,
+            | RayKind::Shadow => self.shadow,
+            | RayKind::Reflection => self.reflection,
+            | RayKind::Refraction => self.refraction,
+        }
+    }
 }
 
 #[derive(Debug)]
 pub struct Sphere {
     id: Uuid,
     pub transform: Matrix,
+    pub end_transform: Option<Matrix>,
+    pub visibility: Visibility,
     pub material: Material,
 }
 
@@ -98,6 +186,8 @@ pub struct Sphere {
 pub struct Plane {
     id: Uuid,
     pub transform: Matrix,
+    pub end_transform: Option<Matrix>,
+    pub visibility: Visibility,
     pub material: Material,
 }
 
@@ -105,6 +195,8 @@ pub struct Plane {
 pub struct TestShape {
     id: Uuid,
     pub transform: Matrix,
+    pub end_transform: Option<Matrix>,
+    pub visibility: Visibility,
     pub saved_ray: Ray,
     pub material: Material,
 }
@@ -113,6 +205,8 @@ pub struct TestShape {
 pub struct Cube {
     id: Uuid,
     pub transform: Matrix,
+    pub end_transform: Option<Matrix>,
+    pub visibility: Visibility,
     pub material: Material,
 }
 
@@ -120,6 +214,8 @@ pub struct Cube {
 pub struct Cylinder {
     id: Uuid,
     pub transform: Matrix,
+    pub end_transform: Option<Matrix>,
+    pub visibility: Visibility,
     pub material: Material,
     pub minimum: f32,
     pub maximum: f32,
@@ -130,6 +226,8 @@ pub struct Cylinder {
 pub struct Cone {
     id: Uuid,
     pub transform: Matrix,
+    pub end_transform: Option<Matrix>,
+    pub visibility: Visibility,
     pub material: Material,
     pub minimum: f32,
     pub maximum: f32,
@@ -140,6 +238,22 @@ pub struct Cone {
 pub struct Group {
     id: Uuid,
     transform: Matrix,
+    end_transform: Option<Matrix>,
+    visibility: Visibility,
+}
+
+pub struct ConstantMedium {
+    id: Uuid,
+    pub boundary: Box<dyn Shape>,
+    pub density: f32,
+    pub material: Material,
+    pub visibility: Visibility,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Fog {
+    pub color: Color,
+    pub density: f32,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -312,7 +426,23 @@ impl Shape for Sphere {
         self.material = material;
     }
 
-    fn local_intersect(&self, ray: Ray) -> Vec<Intersection> {
+    fn end_transform(&self) -> Option<Matrix> {
+        self.end_transform.clone()
+    }
+
+    fn set_end_transform(&mut self, transform: Option<Matrix>) {
+        self.end_transform = transform;
+    }
+
+    fn visibility(&self) -> Visibility {
+        self.visibility
+    }
+
+    fn set_visibility(&mut self, visibility: Visibility) {
+        self.visibility = visibility;
+    }
+
+    fn local_intersect(&self, ray: Ray) -> Vec<Intersection<'_>> {
         let sphere_to_ray = ray.origin - point(0.0, 0.0, 0.0);
 
         let a = dot(ray.direction, ray.direction);
@@ -365,7 +495,23 @@ impl Shape for Plane {
         self.material = material;
     }
 
-    fn local_intersect(&self, ray: Ray) -> Vec<Intersection> {
+    fn end_transform(&self) -> Option<Matrix> {
+        self.end_transform.clone()
+    }
+
+    fn set_end_transform(&mut self, transform: Option<Matrix>) {
+        self.end_transform = transform;
+    }
+
+    fn visibility(&self) -> Visibility {
+        self.visibility
+    }
+
+    fn set_visibility(&mut self, visibility: Visibility) {
+        self.visibility = visibility;
+    }
+
+    fn local_intersect(&self, ray: Ray) -> Vec<Intersection<'_>> {
         match ray.direction.y.abs() < EPS {
             | true => [].to_vec(),
             | _ => [intersection(-ray.origin.y / ray.direction.y, self)].to_vec()
@@ -406,7 +552,23 @@ impl Shape for TestShape {
         self.material = material;
     }
 
-    fn local_intersect(&self, _ray: Ray) -> Vec<Intersection> {
+    fn end_transform(&self) -> Option<Matrix> {
+        self.end_transform.clone()
+    }
+
+    fn set_end_transform(&mut self, transform: Option<Matrix>) {
+        self.end_transform = transform;
+    }
+
+    fn visibility(&self) -> Visibility {
+        self.visibility
+    }
+
+    fn set_visibility(&mut self, visibility: Visibility) {
+        self.visibility = visibility;
+    }
+
+    fn local_intersect(&self, _ray: Ray) -> Vec<Intersection<'_>> {
         [].to_vec()
     }
 
@@ -432,7 +594,15 @@ impl Shape for Cube {
 
     fn set_material(&mut self, material: Material) { self.material = material; }
 
-    fn local_intersect(&self, ray: Ray) -> Vec<Intersection> {
+    fn end_transform(&self) -> Option<Matrix> { self.end_transform.clone() }
+
+    fn set_end_transform(&mut self, transform: Option<Matrix>) { self.end_transform = transform; }
+
+    fn visibility(&self) -> Visibility { self.visibility }
+
+    fn set_visibility(&mut self, visibility: Visibility) { self.visibility = visibility; }
+
+    fn local_intersect(&self, ray: Ray) -> Vec<Intersection<'_>> {
         let (xt_min, xt_max) = check_axis(ray.origin.x, ray.direction.x);
         let (yt_min, yt_max) = check_axis(ray.origin.y, ray.direction.y);
         let (zt_min, zt_max) = check_axis(ray.origin.z, ray.direction.z);
@@ -476,7 +646,15 @@ impl Shape for Cylinder {
 
     fn set_material(&mut self, material: Material) { self.material = material; }
 
-    fn local_intersect(&self, ray: Ray) -> Vec<Intersection> {
+    fn end_transform(&self) -> Option<Matrix> { self.end_transform.clone() }
+
+    fn set_end_transform(&mut self, transform: Option<Matrix>) { self.end_transform = transform; }
+
+    fn visibility(&self) -> Visibility { self.visibility }
+
+    fn set_visibility(&mut self, visibility: Visibility) { self.visibility = visibility; }
+
+    fn local_intersect(&self, ray: Ray) -> Vec<Intersection<'_>> {
         let a = ray.direction.x.powf(2.0) + ray.direction.z.powf(2.0);
 
         if a < EPS {
@@ -535,7 +713,15 @@ impl Shape for Cone {
 
     fn set_material(&mut self, material: Material) { self.material = material; }
 
-    fn local_intersect(&self, ray: Ray) -> Vec<Intersection> {
+    fn end_transform(&self) -> Option<Matrix> { self.end_transform.clone() }
+
+    fn set_end_transform(&mut self, transform: Option<Matrix>) { self.end_transform = transform; }
+
+    fn visibility(&self) -> Visibility { self.visibility }
+
+    fn set_visibility(&mut self, visibility: Visibility) { self.visibility = visibility; }
+
+    fn local_intersect(&self, ray: Ray) -> Vec<Intersection<'_>> {
         let a = ray.direction.x.powf(2.0) - ray.direction.y.powf(2.0) + ray.direction.z.powf(2.0);
 
         let b = 2.0 * ray.origin.x * ray.direction.x -
@@ -608,15 +794,31 @@ impl Shape for Group {
         self.transform = transform;
     }
 
-    fn set_material(&mut self, material: Material) {
+    fn set_material(&mut self, _material: Material) {
         todo!()
     }
 
-    fn local_intersect(&self, ray: Ray) -> Vec<Intersection> {
+    fn end_transform(&self) -> Option<Matrix> {
+        self.end_transform.clone()
+    }
+
+    fn set_end_transform(&mut self, transform: Option<Matrix>) {
+        self.end_transform = transform;
+    }
+
+    fn visibility(&self) -> Visibility {
+        self.visibility
+    }
+
+    fn set_visibility(&mut self, visibility: Visibility) {
+        self.visibility = visibility;
+    }
+
+    fn local_intersect(&self, _ray: Ray) -> Vec<Intersection<'_>> {
         todo!()
     }
 
-    fn local_normal_at(&self, point: Point) -> Vector {
+    fn local_normal_at(&self, _point: Point) -> Vector {
         todo!()
     }
 
@@ -625,6 +827,40 @@ impl Shape for Group {
     }
 }
 
+impl Shape for ConstantMedium {
+    fn id(&self) -> Uuid { self.id }
+
+    fn transform(&self) -> Matrix { self.boundary.transform() }
+
+    fn material(&self) -> &Material { &self.material }
+
+    fn mut_material(&mut self) -> &mut Material { &mut self.material }
+
+    fn set_transform(&mut self, transform: Matrix) { self.boundary.set_transform(transform) }
+
+    fn set_material(&mut self, material: Material) { self.material = material }
+
+    fn end_transform(&self) -> Option<Matrix> { self.boundary.end_transform() }
+
+    fn set_end_transform(&mut self, transform: Option<Matrix>) { self.boundary.set_end_transform(transform) }
+
+    fn visibility(&self) -> Visibility { self.visibility }
+
+    fn set_visibility(&mut self, visibility: Visibility) { self.visibility = visibility }
+
+    fn local_intersect(&self, ray: Ray) -> Vec<Intersection<'_>> {
+        self.boundary.local_intersect(ray).iter().map(|i| intersection(i.t, self)).collect()
+    }
+
+    fn local_normal_at(&self, point: Point) -> Vector {
+        self.boundary.local_normal_at(point)
+    }
+
+    fn as_any(&self) -> &dyn Any { self }
+
+    fn is_volume(&self) -> bool { true }
+}
+
 #[derive(Clone, Copy)]
 pub struct Intersection<'a> {
     pub t: f32,
@@ -646,14 +882,58 @@ pub struct Material {
     pub reflective: f32,
     pub transparency: f32,
     pub refractive_index: f32,
+    pub dispersion: f32,
+    pub emissive: Color,
+    pub emissive_strength: f32,
+    pub shading: Shading,
+    pub metallic: f32,
+    pub roughness: f32,
+    pub fresnel: Fresnel,
+    pub absorption: Color,
+    pub absorption_density: f32,
     pub pattern: Option<Box<dyn Pattern>>,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Fresnel {
+    Schlick,
+    Exact,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Shading {
+    Phong,
+    Microfacet,
+}
+
 pub struct World {
     pub objects: Vec<Box<dyn Shape>>,
     pub lights: Vec<PointLight>,
+    pub background: Background,
+    pub environment_light: Option<EnvironmentLight>,
+    pub fog: Option<Fog>,
+}
+
+pub enum Background {
+    Solid(Color),
+    Gradient { top: Color, bottom: Color },
+    Environment(EnvironmentMap),
+}
+
+pub struct EnvironmentMap {
+    image: Canvas,
+    pub intensity: f32,
+    marginal_cdf: Vec<f32>,
+    conditional_cdf: Vec<f32>,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EnvironmentLight {
+    pub samples: u32,
+    pub intensity: f32,
+}
+
+#[derive(Clone, Copy)]
 pub struct Computation<'a> {
     pub t: f32,
     pub object: &'a dyn Shape,
@@ -666,6 +946,10 @@ pub struct Computation<'a> {
     pub reflect_v: Vector,
     pub n1: f32,
     pub n2: f32,
+    pub n1_dispersion: f32,
+    pub n2_dispersion: f32,
+    pub time: f32,
+    pub channel: Option<usize>,
 }
 
 pub struct Camera {
@@ -676,6 +960,180 @@ pub struct Camera {
     pub field_of_view: f32,
     pub transform: Matrix,
     pub pixel_size: f32,
+    pub shutter_open: f32,
+    pub shutter_close: f32,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Quaternion {
+    pub w: f32,
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Keyframe {
+    pub time: f32,
+    pub translation: Vector,
+    pub rotation: Quaternion,
+    pub scale: Vector,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyframeTrack {
+    pub keyframes: Vec<Keyframe>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Integrator {
+    Whitted,
+    PathTracer,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RenderSettings {
+    pub max_reflection_depth: u8,
+    pub max_refraction_depth: u8,
+    pub min_contribution: f32,
+    pub max_path_depth: u8,
+    pub samples: u32,
+    /// 0 uses one thread per available core
+    pub threads: usize,
+    pub tile_size: i32,
+    pub integrator: Integrator,
+    pub crop: Option<Tile>,
+    /// Return only the cropped pixels instead of a full size canvas
+    pub crop_output: bool,
+    pub tone_mapping: ToneMapping,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ToneMap {
+    Linear,
+    Reinhard,
+    Aces,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ToneMapping {
+    /// In stops, every stop doubles the brightness
+    pub exposure: f32,
+    pub operator: ToneMap,
+    pub srgb: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Tile {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RenderProgress {
+    pub tile: Tile,
+    pub completed: usize,
+    pub total: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct RayNode {
+    pub ray: Ray,
+    pub hit: Option<RayHit>,
+    pub color: Color,
+    /// What the hit surface added to `color`, `reflected` and `refracted` hold the rest
+    pub surface: Color,
+    pub reflected: Color,
+    pub refracted: Color,
+    pub children: Vec<RayNode>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RayHit {
+    pub object: Uuid,
+    pub t: f32,
+    pub point: Point,
+    pub normal_v: Vector,
+    pub inside: bool,
+    pub n1: f32,
+    pub n2: f32,
+}
+
+pub struct Aovs {
+    /// Distance to the first hit along the camera's view axis
+    pub depth: Canvas,
+    pub normal: Canvas,
+    pub albedo: Canvas,
+    pub object_id: Canvas,
+    pub shadow: Canvas,
+}
+
+/// A sigma of zero turns its term of the filter off
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DenoiseSettings {
+    pub radius: i32,
+    pub spatial_sigma: f32,
+    pub color_sigma: f32,
+    pub normal_sigma: f32,
+    pub albedo_sigma: f32,
+}
+
+struct RayRecorder {
+    open: Vec<RayNode>,
+    roots: Vec<RayNode>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RayBudget {
+    pub reflections: u8,
+    pub refractions: u8,
+    /// A single depth shared by reflections and refractions, as the u8 entry points count it
+    pub depth: Option<u8>,
+    pub contribution: f32,
+    pub min_contribution: f32,
+}
+
+impl RenderSettings {
+    pub fn ray_budget(&self) -> RayBudget {
+        RayBudget {
+            reflections: self.max_reflection_depth,
+            refractions: self.max_refraction_depth,
+            depth: None,
+            contribution: 1.0,
+            min_contribution: self.min_contribution,
+        }
+    }
+}
+
+impl RayBudget {
+    fn reflect(&self, weight: f32) -> Option<RayBudget> {
+        let contribution = self.contribution * weight;
+        if self.reflections == 0 || self.depth == Some(0) || contribution < self.min_contribution {
+            return None;
+        }
+        Some(RayBudget { reflections: self.reflections - 1, depth: self.depth.map(|d| d - 1), contribution, ..*self })
+    }
+
+    fn refract(&self, weight: f32) -> Option<RayBudget> {
+        let contribution = self.contribution * weight;
+        if self.refractions == 0 || self.depth == Some(0) || contribution < self.min_contribution {
+            return None;
+        }
+        Some(RayBudget { refractions: self.refractions - 1, depth: self.depth.map(|d| d - 1), contribution, ..*self })
+    }
+}
+
+pub type TransformAt = Box<dyn Fn(f32) -> Matrix>;
+pub type LightAt = Box<dyn Fn(f32) -> PointLight>;
+pub type MaterialAt = Box<dyn Fn(f32, &mut Material)>;
+
+pub struct Animation {
+    pub camera: Option<TransformAt>,
+    pub lights: Vec<(usize, LightAt)>,
+    pub objects: Vec<(usize, TransformAt)>,
+    pub materials: Vec<(usize, MaterialAt)>,
 }
 
 impl Cylinder {
@@ -686,7 +1144,7 @@ impl Cylinder {
         (x.powf(2.0) + z.powf(2.0)) <= 1.0
     }
 
-    fn intersect_cap(&self, ray: Ray) -> Vec<Intersection> {
+    fn intersect_cap(&self, ray: Ray) -> Vec<Intersection<'_>> {
         if !self.closed || ray.direction.y.abs() < f32::EPSILON {
             return [].to_vec();
         }
@@ -735,7 +1193,7 @@ impl Cone {
         (x.powf(2.0) + z.powf(2.0)) <= y.abs()
     }
 
-    fn intersect_cap(&self, ray: Ray) -> Vec<Intersection> {
+    fn intersect_cap(&self, ray: Ray) -> Vec<Intersection<'_>> {
         if !self.closed || ray.direction.y.abs() < f32::EPSILON {
             return [].to_vec();
         }
@@ -758,6 +1216,97 @@ impl Group {
     pub fn count(&self) -> usize { 0 }
 }
 
+impl Animation {
+    pub fn animate_camera(&mut self, f: impl Fn(f32) -> Matrix + 'static) {
+        self.camera = Some(Box::new(f));
+    }
+
+    pub fn animate_light(&mut self, index: usize, f: impl Fn(f32) -> PointLight + 'static) {
+        self.lights.push((index, Box::new(f)));
+    }
+
+    pub fn animate_object(&mut self, index: usize, f: impl Fn(f32) -> Matrix + 'static) {
+        self.objects.push((index, Box::new(f)));
+    }
+
+    pub fn animate_material(&mut self, index: usize, f: impl Fn(f32, &mut Material) + 'static) {
+        self.materials.push((index, Box::new(f)));
+    }
+
+    pub fn apply(&self, time: f32, camera: &mut Camera, world: &mut World) -> Result<(), String> {
+        if let Some((index, _)) = self.lights.iter().find(|(i, _)| *i >= world.lights.len()) {
+            return Err(format!("Animated light {index} does not exist, the world has {} lights", world.lights.len()));
+        }
+        let objects = self.objects.iter().map(|(i, _)| *i).chain(self.materials.iter().map(|(i, _)| *i));
+        if let Some(index) = objects.into_iter().find(|i| *i >= world.objects.len()) {
+            return Err(format!("Animated object {index} does not exist, the world has {} objects", world.objects.len()));
+        }
+
+        if let Some(f) = &self.camera {
+            camera.transform = f(time);
+        }
+        for (index, f) in &self.lights {
+            world.lights[*index] = f(time);
+        }
+        for (index, f) in &self.objects {
+            world.objects[*index].set_transform(f(time));
+        }
+        for (index, f) in &self.materials {
+            f(time, world.objects[*index].mut_material());
+        }
+        Ok(())
+    }
+}
+
+impl Quaternion {
+    pub fn identity() -> Self {
+        quaternion(1.0, 0.0, 0.0, 0.0)
+    }
+}
+
+impl EnvironmentMap {
+    pub fn image(&self) -> &Canvas {
+        &self.image
+    }
+}
+
+impl KeyframeTrack {
+    pub fn add(&mut self, k: Keyframe) {
+        let idx = self.keyframes.iter().position(|e| e.time > k.time).unwrap_or(self.keyframes.len());
+        self.keyframes.insert(idx, k);
+    }
+
+    pub fn sample(&self, time: f32) -> Option<Keyframe> {
+        let first = self.keyframes.first()?;
+        let last = self.keyframes.last()?;
+        if time <= first.time {
+            return Some(*first);
+        }
+        if time >= last.time {
+            return Some(*last);
+        }
+
+        let next = self.keyframes.iter().position(|k| k.time > time)?;
+        let a = self.keyframes[next - 1];
+        let b = self.keyframes[next];
+        let t = (time - a.time) / (b.time - a.time);
+
+        Some(keyframe(time,
+                      a.translation + (b.translation - a.translation) * t,
+                      slerp(a.rotation, b.rotation, t),
+                      a.scale + (b.scale - a.scale) * t))
+    }
+
+    pub fn transform_at(&self, time: f32) -> Matrix {
+        match self.sample(time) {
+            | None => Matrix::identity4x4(),
+            | Some(k) => translation(k.translation.x, k.translation.y, k.translation.z) *
+                quaternion_to_matrix(k.rotation) *
+                scaling(k.scale.x, k.scale.y, k.scale.z)
+        }
+    }
+}
+
 impl Canvas {
     pub fn new(width: i32, height: i32) -> Self {
         Canvas {width , height, pixels: vec![color(0.0,0.0,0.0); (width * height) as usize]}
@@ -772,47 +1321,263 @@ impl Canvas {
         let idx = (y*self.width + x) as usize;
         self.pixels[idx]
     }
-}
-
-impl Matrix {
-    pub fn new2x2(row1: [f32; 2], row2: [f32; 2]) -> Self {
 
-        let mut elems = vec![0.0; row1.len() * row2.len()];
-
-        for (i,e) in row1.iter().enumerate() {
-            elems[i] = *e;
-        }
+    pub fn try_pixel_at(&self, x: i32, y: i32) -> Option<Color> {
+        if self.contains(x, y) { Some(self.pixel_at(x, y)) } else { None }
+    }
 
-        for (i,e) in row2.iter().enumerate() {
-            elems[2 + i] = *e;
+    pub fn try_write_pixel(&mut self, x: i32, y: i32, c: Color) -> Result<(), String> {
+        if !self.contains(x, y) {
+            return Err(format!("pixel ({x}, {y}) is outside a {}x{} canvas", self.width, self.height));
         }
+        self.write_pixel(x, y, c);
+        Ok(())
+    }
 
-        Matrix { elems, size: 2 }
+    pub fn contains(&self, x: i32, y: i32) -> bool {
+        x >= 0 && y >= 0 && x < self.width && y < self.height
     }
 
-    pub fn new3x3(
-        row1: [f32; 3],
-        row2: [f32; 3],
-        row3: [f32; 3]) -> Self {
+    fn clamped_pixel_at(&self, x: i32, y: i32) -> Color {
+        self.pixel_at(x.clamp(0, self.width - 1), y.clamp(0, self.height - 1))
+    }
 
-        let mut elems = vec![0.0; row1.len() * row2.len()];
+    pub fn map<F>(&self, f: F) -> Canvas where F: Fn(Color) -> Color {
+        Canvas { width: self.width, height: self.height, pixels: self.pixels.iter().map(|p| f(*p)).collect() }
+    }
 
-        for (i,e) in row1.iter().enumerate() {
-            elems[i] = *e;
+    pub fn crop(&self, x: i32, y: i32, width: i32, height: i32) -> Result<Canvas, String> {
+        if width <= 0 || height <= 0 {
+            return Err(format!("Crop size must be positive, got {width}x{height}"));
         }
-
-        for (i,e) in row2.iter().enumerate() {
-            elems[3 + i] = *e;
+        let (x0, y0) = (x.clamp(0, self.width), y.clamp(0, self.height));
+        let (x1, y1) = ((x + width).clamp(x0, self.width), (y + height).clamp(y0, self.height));
+        let mut c = Canvas::new(x1 - x0, y1 - y0);
+        for cy in 0..c.height {
+            for cx in 0..c.width {
+                c.write_pixel(cx, cy, self.pixel_at(x0 + cx, y0 + cy));
+            }
         }
+        Ok(c)
+    }
 
-        for (i,e) in row3.iter().enumerate() {
-            elems[6 + i] = *e;
+    pub fn flip_horizontal(&self) -> Canvas {
+        let mut c = Canvas::new(self.width, self.height);
+        for y in 0..self.height {
+            for x in 0..self.width {
+                c.write_pixel(x, y, self.pixel_at(self.width - 1 - x, y));
+            }
         }
+        c
+    }
 
-        Matrix { elems, size: 3 }
+    pub fn flip_vertical(&self) -> Canvas {
+        let mut c = Canvas::new(self.width, self.height);
+        for y in 0..self.height {
+            for x in 0..self.width {
+                c.write_pixel(x, y, self.pixel_at(x, self.height - 1 - y));
+            }
+        }
+        c
     }
 
-    pub fn new4x4(
+    pub fn resize(&self, width: i32, height: i32, filter: ResizeFilter) -> Result<Canvas, String> {
+        if width <= 0 || height <= 0 {
+            return Err(format!("Resize size must be positive, got {width}x{height}"));
+        }
+        let mut c = Canvas::new(width, height);
+        if self.width == 0 || self.height == 0 {
+            return Ok(c);
+        }
+        let sx = self.width as f32 / width as f32;
+        let sy = self.height as f32 / height as f32;
+        for y in 0..height {
+            for x in 0..width {
+                let p = match filter {
+                    | ResizeFilter::Nearest =>
+                        self.clamped_pixel_at(((x as f32 + 0.5) * sx) as i32, ((y as f32 + 0.5) * sy) as i32),
+                    | ResizeFilter::Bilinear => {
+                        let u = (x as f32 + 0.5) * sx - 0.5;
+                        let v = (y as f32 + 0.5) * sy - 0.5;
+                        let (x0, y0) = (u.floor() as i32, v.floor() as i32);
+                        let (fx, fy) = (u - u.floor(), v - v.floor());
+                        let top = self.clamped_pixel_at(x0, y0) * (1.0 - fx) + self.clamped_pixel_at(x0 + 1, y0) * fx;
+                        let bottom = self.clamped_pixel_at(x0, y0 + 1) * (1.0 - fx) + self.clamped_pixel_at(x0 + 1, y0 + 1) * fx;
+                        top * (1.0 - fy) + bottom * fy
+                    }
+                    | ResizeFilter::Box => {
+                        let (x0, y0) = ((x as f32 * sx) as i32, (y as f32 * sy) as i32);
+                        let x1 = (((x + 1) as f32 * sx).ceil() as i32).max(x0 + 1);
+                        let y1 = (((y + 1) as f32 * sy).ceil() as i32).max(y0 + 1);
+                        let mut sum = color(0.0, 0.0, 0.0);
+                        for py in y0..y1 {
+                            for px in x0..x1 {
+                                sum = sum + self.clamped_pixel_at(px, py);
+                            }
+                        }
+                        sum * (1.0 / ((x1 - x0) * (y1 - y0)) as f32)
+                    }
+                };
+                c.write_pixel(x, y, p);
+            }
+        }
+        Ok(c)
+    }
+
+    pub fn blend(&self, top: &Canvas, x: i32, y: i32, mode: BlendMode, opacity: f32) -> Canvas {
+        let mut c = self.clone();
+        for ty in 0..top.height {
+            for tx in 0..top.width {
+                let (bx, by) = (x + tx, y + ty);
+                if !self.contains(bx, by) {
+                    continue;
+                }
+                let a = self.pixel_at(bx, by);
+                let b = top.pixel_at(tx, ty);
+                let blended = match mode {
+                    | BlendMode::Normal => b,
+                    | BlendMode::Add => a + b,
+                    | BlendMode::Multiply => a * b,
+                    | BlendMode::Screen => {
+                        let white = color(1.0, 1.0, 1.0);
+                        white - (white - a) * (white - b)
+                    }
+                };
+                c.write_pixel(bx, by, a * (1.0 - opacity) + blended * opacity);
+            }
+        }
+        c
+    }
+
+    pub fn convolve(&self, kernel: &Kernel) -> Canvas {
+        let (cx, cy) = (kernel.width / 2, kernel.height / 2);
+        let mut c = Canvas::new(self.width, self.height);
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let mut sum = color(0.0, 0.0, 0.0);
+                for ky in 0..kernel.height {
+                    for kx in 0..kernel.width {
+                        let weight = kernel.weights[(ky * kernel.width + kx) as usize];
+                        sum = sum + self.clamped_pixel_at(x + kx - cx, y + ky - cy) * weight;
+                    }
+                }
+                c.write_pixel(x, y, sum);
+            }
+        }
+        c
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ImageDiff {
+    pub rmse: f32,
+    /// Peak signal to noise ratio in dB, infinite for identical images
+    pub psnr: f32,
+    pub max_diff: f32,
+    pub heat_map: Canvas,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ResizeFilter {
+    Nearest,
+    Bilinear,
+    Box,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BlendMode {
+    Normal,
+    Add,
+    Multiply,
+    Screen,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Kernel {
+    pub width: i32,
+    pub height: i32,
+    pub weights: Vec<f32>,
+}
+
+pub fn kernel(width: i32, height: i32, weights: Vec<f32>) -> Result<Kernel, String> {
+    if width <= 0 || height <= 0 {
+        return Err(format!("Kernel must not be empty, got {width}x{height}"));
+    }
+    if weights.len() != (width * height) as usize {
+        return Err(format!("A {width}x{height} kernel needs {} weights, got {}", width * height, weights.len()));
+    }
+    Ok(Kernel { width, height, weights })
+}
+
+pub fn box_blur(radius: i32) -> Result<Kernel, String> {
+    if radius < 0 {
+        return Err(format!("Blur radius must not be negative, got {radius}"));
+    }
+    let size = 2 * radius + 1;
+    kernel(size, size, vec![1.0 / (size * size) as f32; (size * size) as usize])
+}
+
+pub fn gaussian_blur(radius: i32, sigma: f32) -> Result<Kernel, String> {
+    if radius < 0 {
+        return Err(format!("Blur radius must not be negative, got {radius}"));
+    }
+    if !(sigma.is_finite() && sigma > 0.0) {
+        return Err(format!("Gaussian sigma must be positive, got {sigma}"));
+    }
+    let size = 2 * radius + 1;
+    let mut weights = vec![];
+    for y in -radius..=radius {
+        for x in -radius..=radius {
+            weights.push((-((x * x + y * y) as f32) / (2.0 * sigma * sigma)).exp());
+        }
+    }
+    let total: f32 = weights.iter().sum();
+    kernel(size, size, weights.iter().map(|w| w / total).collect())
+}
+
+pub fn sharpen() -> Kernel {
+    Kernel { width: 3, height: 3, weights: vec![0.0, -1.0, 0.0, -1.0, 5.0, -1.0, 0.0, -1.0, 0.0] }
+}
+
+impl Matrix {
+    pub fn new2x2(row1: [f32; 2], row2: [f32; 2]) -> Self {
+
+        let mut elems = vec![0.0; row1.len() * row2.len()];
+
+        for (i,e) in row1.iter().enumerate() {
+            elems[i] = *e;
+        }
+
+        for (i,e) in row2.iter().enumerate() {
+            elems[2 + i] = *e;
+        }
+
+        Matrix { elems, size: 2 }
+    }
+
+    pub fn new3x3(
+        row1: [f32; 3],
+        row2: [f32; 3],
+        row3: [f32; 3]) -> Self {
+
+        let mut elems = vec![0.0; row1.len() * row2.len()];
+
+        for (i,e) in row1.iter().enumerate() {
+            elems[i] = *e;
+        }
+
+        for (i,e) in row2.iter().enumerate() {
+            elems[3 + i] = *e;
+        }
+
+        for (i,e) in row3.iter().enumerate() {
+            elems[6 + i] = *e;
+        }
+
+        Matrix { elems, size: 3 }
+    }
+
+    pub fn new4x4(
         row1: [f32; 4],
         row2: [f32; 4],
         row3: [f32; 4],
@@ -955,6 +1720,19 @@ impl Mul<Tuple> for Matrix {
     }
 }
 
+impl Mul<Quaternion> for Quaternion {
+    type Output = Quaternion;
+
+    fn mul(self, rhs: Quaternion) -> Self::Output {
+        Quaternion {
+            w: self.w * rhs.w - self.x * rhs.x - self.y * rhs.y - self.z * rhs.z,
+            x: self.w * rhs.x + self.x * rhs.w + self.y * rhs.z - self.z * rhs.y,
+            y: self.w * rhs.y - self.x * rhs.z + self.y * rhs.w + self.z * rhs.x,
+            z: self.w * rhs.z + self.x * rhs.y - self.y * rhs.x + self.z * rhs.w,
+        }
+    }
+}
+
 impl Div<f32> for Tuple {
     type Output = Self;
 
@@ -997,6 +1775,15 @@ impl PartialEq for Matrix {
     }
 }
 
+impl PartialEq for Quaternion {
+    fn eq(&self, rhs: &Self) -> bool {
+        self.w.sub(rhs.w).abs() < EPS &&
+            self.x.sub(rhs.x).abs() < EPS &&
+            self.y.sub(rhs.y).abs() < EPS &&
+            self.z.sub(rhs.z).abs() < EPS
+    }
+}
+
 impl<'a> PartialEq for Intersection<'a> {
     fn eq(&self, other: &Self) -> bool {
         self.t == other.t && self.object.id() == other.object.id()
@@ -1206,15 +1993,21 @@ pub fn inverse(a: &Matrix) -> Matrix {
     let mut values = [[0.0; 4]; 4];
     let size = a.clone().size;
     let determinant = determinant(a.clone());
-    for row in 0..size {
-        for col in 0..size {
+    for (col, column) in values.iter_mut().enumerate().take(size) {
+        for (row, value) in column.iter_mut().enumerate().take(size) {
             let c = cofactor(a.clone(), row, col);
 
-            values[col][row] = c / determinant;
+            *value = c / determinant;
         }
     }
 
-    Matrix::new4x4(values[0], values[1], values[2], values[3])
+    match size {
+        | 2 => Matrix::new2x2([values[0][0], values[0][1]], [values[1][0], values[1][1]]),
+        | 3 => Matrix::new3x3([values[0][0], values[0][1], values[0][2]],
+                              [values[1][0], values[1][1], values[1][2]],
+                              [values[2][0], values[2][1], values[2][2]]),
+        | _ => Matrix::new4x4(values[0], values[1], values[2], values[3])
+    }
 }
 
 pub fn translation(tx: f32, ty: f32, tz: f32) -> Matrix {
@@ -1263,8 +2056,173 @@ pub fn shearing(sxy: f32, sxz: f32, syx: f32, syz: f32, szx: f32, szy: f32) -> M
         [0.0, 0.0, 0.0, 1.0])
 }
 
+pub fn quaternion(w: f32, x: f32, y: f32, z: f32) -> Quaternion {
+    Quaternion { w, x, y, z }
+}
+
+pub fn rotation_quaternion(axis: Vector, r: f32) -> Quaternion {
+    let axis = normalize(axis);
+    let s = (r / 2.0).sin();
+    quaternion((r / 2.0).cos(), axis.x * s, axis.y * s, axis.z * s)
+}
+
+pub fn normalize_quaternion(q: Quaternion) -> Quaternion {
+    let magnitude = (q.w * q.w + q.x * q.x + q.y * q.y + q.z * q.z).sqrt();
+    quaternion(q.w / magnitude, q.x / magnitude, q.y / magnitude, q.z / magnitude)
+}
+
+pub fn quaternion_to_matrix(q: Quaternion) -> Matrix {
+    let q = normalize_quaternion(q);
+    let (w, x, y, z) = (q.w, q.x, q.y, q.z);
+    Matrix::new4x4(
+        [1.0 - 2.0 * (y * y + z * z), 2.0 * (x * y - w * z), 2.0 * (x * z + w * y), 0.0],
+        [2.0 * (x * y + w * z), 1.0 - 2.0 * (x * x + z * z), 2.0 * (y * z - w * x), 0.0],
+        [2.0 * (x * z - w * y), 2.0 * (y * z + w * x), 1.0 - 2.0 * (x * x + y * y), 0.0],
+        [0.0, 0.0, 0.0, 1.0])
+}
+
+pub fn matrix_to_quaternion(m: &Matrix) -> Quaternion {
+    let trace = m.at(0, 0) + m.at(1, 1) + m.at(2, 2);
+    let q = if trace > 0.0 {
+        let s = 0.5 / (trace + 1.0).sqrt();
+        quaternion(0.25 / s,
+                   (m.at(2, 1) - m.at(1, 2)) * s,
+                   (m.at(0, 2) - m.at(2, 0)) * s,
+                   (m.at(1, 0) - m.at(0, 1)) * s)
+    } else if m.at(0, 0) > m.at(1, 1) && m.at(0, 0) > m.at(2, 2) {
+        let s = 2.0 * (1.0 + m.at(0, 0) - m.at(1, 1) - m.at(2, 2)).sqrt();
+        quaternion((m.at(2, 1) - m.at(1, 2)) / s,
+                   0.25 * s,
+                   (m.at(0, 1) + m.at(1, 0)) / s,
+                   (m.at(0, 2) + m.at(2, 0)) / s)
+    } else if m.at(1, 1) > m.at(2, 2) {
+        let s = 2.0 * (1.0 + m.at(1, 1) - m.at(0, 0) - m.at(2, 2)).sqrt();
+        quaternion((m.at(0, 2) - m.at(2, 0)) / s,
+                   (m.at(0, 1) + m.at(1, 0)) / s,
+                   0.25 * s,
+                   (m.at(1, 2) + m.at(2, 1)) / s)
+    } else {
+        let s = 2.0 * (1.0 + m.at(2, 2) - m.at(0, 0) - m.at(1, 1)).sqrt();
+        quaternion((m.at(1, 0) - m.at(0, 1)) / s,
+                   (m.at(0, 2) + m.at(2, 0)) / s,
+                   (m.at(1, 2) + m.at(2, 1)) / s,
+                   0.25 * s)
+    };
+    normalize_quaternion(q)
+}
+
+/// Interpolates along the shorter arc, since q and -q are the same rotation
+pub fn slerp(a: Quaternion, b: Quaternion, t: f32) -> Quaternion {
+    let mut cos = a.w * b.w + a.x * b.x + a.y * b.y + a.z * b.z;
+    let b = if cos < 0.0 {
+        cos = -cos;
+        quaternion(-b.w, -b.x, -b.y, -b.z)
+    } else {
+        b
+    };
+
+    let (wa, wb) = if cos > 1.0 - EPS {
+        (1.0 - t, t)
+    } else {
+        let theta = cos.acos();
+        let sin = theta.sin();
+        (((1.0 - t) * theta).sin() / sin, (t * theta).sin() / sin)
+    };
+
+    normalize_quaternion(quaternion(a.w * wa + b.w * wb,
+                                    a.x * wa + b.x * wb,
+                                    a.y * wa + b.y * wb,
+                                    a.z * wa + b.z * wb))
+}
+
+pub fn decompose(m: &Matrix) -> (Vector, Quaternion, Vector) {
+    let translation = vector(m.at(0, 3), m.at(1, 3), m.at(2, 3));
+    let column = |c: usize| vector(m.at(0, c), m.at(1, c), m.at(2, c));
+    let (c0, c1, c2) = (column(0), column(1), column(2));
+    let mut scale = vector(magnitude(c0), magnitude(c1), magnitude(c2));
+    if dot(cross(c0, c1), c2) < 0.0 {
+        scale.x = -scale.x;
+    }
+    let unit = |c: Vector, s: f32| if s.abs() < EPS { c } else { c / s };
+    let (r0, r1, r2) = (unit(c0, scale.x), unit(c1, scale.y), unit(c2, scale.z));
+    let rotation = Matrix::new4x4(
+        [r0.x, r1.x, r2.x, 0.0],
+        [r0.y, r1.y, r2.y, 0.0],
+        [r0.z, r1.z, r2.z, 0.0],
+        [0.0, 0.0, 0.0, 1.0]);
+
+    (translation, matrix_to_quaternion(&rotation), scale)
+}
+
+pub fn interpolate_transform(a: &Matrix, b: &Matrix, t: f32) -> Matrix {
+    if t <= 0.0 {
+        return a.clone();
+    }
+    if t >= 1.0 {
+        return b.clone();
+    }
+    let (Some((ta, ra, sa)), Some((tb, rb, sb))) = (decompose_trs(a), decompose_trs(b)) else {
+        return lerp_matrix(a, b, t);
+    };
+    let tt = ta + (tb - ta) * t;
+    let st = sa + (sb - sa) * t;
+
+    translation(tt.x, tt.y, tt.z) * quaternion_to_matrix(slerp(ra, rb, t)) * scaling(st.x, st.y, st.z)
+}
+
+fn decompose_trs(m: &Matrix) -> Option<(Vector, Quaternion, Vector)> {
+    let (t, r, s) = decompose(m);
+    if s.x.abs() < EPS || s.y.abs() < EPS || s.z.abs() < EPS {
+        return None;
+    }
+    let recomposed = translation(t.x, t.y, t.z) * quaternion_to_matrix(r) * scaling(s.x, s.y, s.z);
+    if recomposed == *m { Some((t, r, s)) } else { None }
+}
+
+fn lerp_matrix(a: &Matrix, b: &Matrix, t: f32) -> Matrix {
+    let row = |i: usize| [0, 1, 2, 3].map(|j| a.at(i, j) + (b.at(i, j) - a.at(i, j)) * t);
+    Matrix::new4x4(row(0), row(1), row(2), row(3))
+}
+
+pub fn keyframe(time: f32, translation: Vector, rotation: Quaternion, scale: Vector) -> Keyframe {
+    Keyframe { time, translation, rotation, scale }
+}
+
+pub fn keyframe_track() -> KeyframeTrack {
+    KeyframeTrack { keyframes: vec![] }
+}
+
+pub fn visibility() -> Visibility {
+    Visibility { camera: true, shadow: true, reflection: true, refraction: true }
+}
+
 pub fn ray(origin: Point, direction: Vector) -> Ray {
-    Ray { origin, direction }
+    Ray { origin, direction, time: 0.0, kind: RayKind::Camera, channel: None }
+}
+
+pub fn ray_with_time(origin: Point, direction: Vector, time: f32) -> Ray {
+    Ray { origin, direction, time, kind: RayKind::Camera, channel: None }
+}
+
+pub fn ray_of_kind(origin: Point, direction: Vector, time: f32, kind: RayKind) -> Ray {
+    Ray { origin, direction, time, kind, channel: None }
+}
+
+pub fn seed_random(seed: u64) {
+    RANDOM_STATE.with(|s| s.set(seed));
+}
+
+/// Next value in [0, 1) from this thread's splitmix64 generator
+pub fn random_f32() -> f32 {
+    let z = RANDOM_STATE.with(|s| {
+        let state = s.get().wrapping_add(0x9e37_79b9_7f4a_7c15);
+        s.set(state);
+        state
+    });
+    let z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    let z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    let z = z ^ (z >> 31);
+    (z >> 40) as f32 / (1u64 << 24) as f32
 }
 
 pub fn position(ray: Ray, t: f32) -> Point {
@@ -1275,6 +2233,8 @@ pub fn sphere() -> Box<dyn Shape> {
     Box::new(Sphere {
         id: Uuid::new_v4(),
         transform: Matrix::identity4x4(),
+        end_transform: None,
+        visibility: visibility(),
         material: material()})
 }
 
@@ -1285,6 +2245,8 @@ pub fn glass_sphere() -> Box<dyn Shape> {
     Box::new(Sphere {
         id: Uuid::new_v4(),
         transform: Matrix::identity4x4(),
+        end_transform: None,
+        visibility: visibility(),
         material,
     })
 }
@@ -1293,6 +2255,8 @@ pub fn plane() -> Box<dyn Shape> {
     Box::new(Plane {
         id: Uuid::new_v4(),
         transform: Matrix::identity4x4(),
+        end_transform: None,
+        visibility: visibility(),
         material: material()})
 }
 
@@ -1300,6 +2264,8 @@ pub fn test_shape() -> Box<dyn Shape> {
     Box::new(TestShape {
         id: Uuid::new_v4(),
         transform: Matrix::identity4x4(),
+        end_transform: None,
+        visibility: visibility(),
         material: material(),
         saved_ray: ray(point(0.0, 0.0, 0.0), vector(0.0,0.0,0.0)) })
 }
@@ -1308,6 +2274,8 @@ pub fn cube() -> Box<dyn Shape> {
     Box::new( Cube {
         id: Uuid::new_v4(),
         transform: Matrix::identity4x4(),
+        end_transform: None,
+        visibility: visibility(),
         material: material()})
 }
 
@@ -1315,6 +2283,8 @@ pub fn cylinder(minimum: Option<f32>, maximum: Option<f32>, closed: Option<bool>
     Box::new( Cylinder {
         id: Uuid::new_v4(),
         transform: Matrix::identity4x4(),
+        end_transform: None,
+        visibility: visibility(),
         material: material(),
         minimum: minimum.unwrap_or(f32::NEG_INFINITY),
         maximum: maximum.unwrap_or(f32::INFINITY),
@@ -1326,6 +2296,8 @@ pub fn cone(minimum: Option<f32>, maximum: Option<f32>, closed: Option<bool>) ->
     Box::new( Cone {
         id: Uuid::new_v4(),
         transform: Matrix::identity4x4(),
+        end_transform: None,
+        visibility: visibility(),
         material: material(),
         minimum: minimum.unwrap_or(f32::NEG_INFINITY),
         maximum: maximum.unwrap_or(f32::INFINITY),
@@ -1336,17 +2308,35 @@ pub fn cone(minimum: Option<f32>, maximum: Option<f32>, closed: Option<bool>) ->
 pub fn group() -> Box<dyn Shape> {
     Box::new(Group {
         id: Uuid::new_v4(),
-        transform: Matrix::identity4x4()
+        transform: Matrix::identity4x4(),
+        end_transform: None,
+        visibility: visibility(),
     })
 }
 
 
-pub fn intersection(t:f32, object: &dyn Shape) -> Intersection {
+pub fn constant_medium(boundary: Box<dyn Shape>, density: f32) -> Box<dyn Shape> {
+    Box::new(ConstantMedium {
+        id: Uuid::new_v4(),
+        boundary,
+        density,
+        material: material(),
+        visibility: visibility(),
+    })
+}
+
+pub fn fog(color: Color, density: f32) -> Fog {
+    Fog { color, density }
+}
+
+pub fn intersection(t:f32, object: &dyn Shape) -> Intersection<'_> {
     Intersection { t, object }
 }
 
-pub fn intersect_world(w: &World, r: Ray) -> Vec<Intersection> {
-    let mut intersections = w.objects.iter().flat_map(|o| o.intersect(r))
+pub fn intersect_world(w: &World, r: Ray) -> Vec<Intersection<'_>> {
+    let mut intersections = w.objects.iter()
+        .filter(|o| o.visibility().sees(r.kind))
+        .flat_map(|o| o.intersect(r))
         .collect::<Vec<_>>();
     intersections.sort_by(|i, j|
         if i.t <= j.t {
@@ -1358,15 +2348,15 @@ pub fn intersect_world(w: &World, r: Ray) -> Vec<Intersection> {
     intersections
 }
 
-fn calculate_point(hit: Intersection, i: &Intersection, containers: &[Uuid], uuids : &[(Uuid, f32)]) -> Option<f32>
+fn calculate_point(hit: Intersection, i: &Intersection, containers: &[Uuid], uuids : &[(Uuid, f32, f32)]) -> Option<(f32, f32)>
 {
     if *i == hit {
         return if containers.is_empty() {
-            Some(1.0f32)
+            Some((1.0f32, 0.0f32))
         } else {
             let last_uuid = containers.last().unwrap();
             let elem = uuids.iter().find(|e| e.0 == *last_uuid).unwrap();
-            Some(elem.1)
+            Some((elem.1, elem.2))
         }
     }
 
@@ -1375,7 +2365,11 @@ fn calculate_point(hit: Intersection, i: &Intersection, containers: &[Uuid], uui
 
 pub fn prepare_computations<'a>(hit: Intersection<'a>, r: Ray, xs: &'a Vec<Intersection<'a>>) -> Computation<'a> {
     let point = position(r, hit.t);
-    let mut normal_v = hit.object.normal_at(point);
+    let mut normal_v = if hit.object.is_volume() {
+        normalize(-r.direction)
+    } else {
+        hit.object.normal_at_time(point, r.time)
+    };
     let inside = dot(normal_v, -r.direction) < 0.0;
     if inside {
         normal_v = -normal_v;
@@ -1383,7 +2377,7 @@ pub fn prepare_computations<'a>(hit: Intersection<'a>, r: Ray, xs: &'a Vec<Inter
     let reflect_v = reflect(r.direction, normal_v);
     let mut containers : Vec<Uuid> = [].to_vec();
     let uuids_with_refractive_index : Vec<_> = xs.iter().map(|el| (
-        el.object.id(), el.object.material().refractive_index)).collect();
+        el.object.id(), el.object.material().refractive_index, el.object.material().dispersion)).collect();
     let mut n1 = None;
     let mut n2 = None;
 
@@ -1402,6 +2396,11 @@ pub fn prepare_computations<'a>(hit: Intersection<'a>, r: Ray, xs: &'a Vec<Inter
             break;
         }
     }
+    let (mut n1, mut n2) = (n1.unwrap_or((0.0, 0.0)), n2.unwrap_or((0.0, 0.0)));
+    if let Some(channel) = r.channel {
+        n1.0 = refractive_index_at(n1.0, n1.1, RGB_WAVELENGTHS[channel]);
+        n2.0 = refractive_index_at(n2.0, n2.1, RGB_WAVELENGTHS[channel]);
+    }
     Computation {
         t: hit.t,
         object: hit.object,
@@ -1412,8 +2411,12 @@ pub fn prepare_computations<'a>(hit: Intersection<'a>, r: Ray, xs: &'a Vec<Inter
         over_point: point + normal_v * EPS,
         under_point: point - normal_v * EPS,
         reflect_v,
-        n1: n1.unwrap_or(0.0),
-        n2: n2.unwrap_or(0.0),
+        n1: n1.0,
+        n2: n2.0,
+        n1_dispersion: n1.1,
+        n2_dispersion: n2.1,
+        time: r.time,
+        channel: r.channel,
     }
 }
 
@@ -1429,7 +2432,7 @@ pub fn hit<'a>(xs: &[Intersection<'a>]) -> Option<Intersection<'a>> {
 }
 
 pub fn transform(r: Ray, m: Matrix) -> Ray {
-    Ray {origin: m.clone()*r.origin, direction: m*r.direction }
+    Ray {origin: m.clone()*r.origin, direction: m*r.direction, time: r.time, kind: r.kind, channel: r.channel }
 }
 
 pub fn reflect(i: Vector, normal: Vector) -> Vector {
@@ -1451,15 +2454,77 @@ pub fn material() -> Material {
         reflective: 0.0,
         transparency: 0.0,
         refractive_index: 1.0,
+        dispersion: 0.0,
+        emissive: color(0.0, 0.0, 0.0),
+        emissive_strength: 1.0,
+        shading: Shading::Phong,
+        metallic: 0.0,
+        roughness: 0.5,
+        fresnel: Fresnel::Schlick,
+        absorption: color(1.0, 1.0, 1.0),
+        absorption_density: 0.0,
     }
 }
 
-pub fn lightning(m: &Material, object: &dyn Shape, l: &PointLight, point: Point, eye_v: Vector, normal_v: Vector, in_shadow: bool) -> Color {
+pub fn microfacet_brdf(m: &Material, base: Color, normal_v: Vector, eye_v: Vector, light_v: Vector) -> (Color, Color) {
+    let white = color(1.0, 1.0, 1.0);
+    let n_dot_l = dot(normal_v, light_v);
+    let n_dot_v = dot(normal_v, eye_v);
+    if n_dot_l <= 0.0 || n_dot_v <= 0.0 {
+        return (color(0.0, 0.0, 0.0), color(0.0, 0.0, 0.0));
+    }
+    let half_v = normalize(light_v + eye_v);
+    let n_dot_h = dot(normal_v, half_v).max(0.0);
+    let v_dot_h = dot(eye_v, half_v).max(0.0);
+
+    let roughness = m.roughness.clamp(0.03, 1.0);
+    let metallic = m.metallic.clamp(0.0, 1.0);
+
+    let alpha2 = roughness.powi(4);
+    let d = alpha2 / (std::f32::consts::PI * (n_dot_h * n_dot_h * (alpha2 - 1.0) + 1.0).powi(2));
+
+    let k = (roughness + 1.0).powi(2) / 8.0;
+    let g = n_dot_l / (n_dot_l * (1.0 - k) + k) * n_dot_v / (n_dot_v * (1.0 - k) + k);
+
+    let f0 = white * (0.04 * (1.0 - metallic)) + base * metallic;
+    let f = f0 + (white - f0) * (1.0 - v_dot_h).powi(5);
 
-    let color_for_lightning = match m.pattern.as_deref() {
+    let specular = f * (d * g / (4.0 * n_dot_l * n_dot_v));
+    let diffuse = (white - f) * base * ((1.0 - metallic) / std::f32::consts::PI);
+    (diffuse, specular)
+}
+
+pub fn emitted(m: &Material) -> Color {
+    m.emissive * m.emissive_strength
+}
+
+pub fn surface_color(m: &Material, object: &dyn Shape, point: Point) -> Color {
+    surface_color_at(m, object, point, 0.0)
+}
+
+pub fn surface_color_at(m: &Material, object: &dyn Shape, point: Point, time: f32) -> Color {
+    match m.pattern.as_deref() {
         | None => m.color,
-        | Some(p) => p.pattern_at_shape(object, point)
-    };
+        | Some(p) => p.pattern_at_shape_time(object, point, time)
+    }
+}
+
+pub fn lightning(m: &Material, object: &dyn Shape, l: &PointLight, point: Point, eye_v: Vector, normal_v: Vector, in_shadow: bool) -> Color {
+    let visible = if in_shadow { color(0.0, 0.0, 0.0) } else { color(1.0, 1.0, 1.0) };
+    lightning_filtered(m, object, l, point, eye_v, normal_v, visible)
+}
+
+pub fn lightning_filtered(m: &Material, object: &dyn Shape, l: &PointLight, point: Point, eye_v: Vector, normal_v: Vector, visible: Color) -> Color {
+    lightning_of(m, surface_color(m, object, point), l, point, eye_v, normal_v, visible)
+}
+
+fn lightning_of(m: &Material, base: Color, l: &PointLight, point: Point, eye_v: Vector, normal_v: Vector, visible: Color) -> Color {
+    let (ambient, diffuse, specular) = lightning_terms(m, base, l, point, eye_v, normal_v);
+
+    ambient + (diffuse + specular) * visible
+}
+
+fn lightning_terms(m: &Material, color_for_lightning: Color, l: &PointLight, point: Point, eye_v: Vector, normal_v: Vector) -> (Color, Color, Color) {
     let effective_color = color_for_lightning * l.intensity;
 
     let light_v = normalize(l.position - point);
@@ -1470,6 +2535,10 @@ pub fn lightning(m: &Material, object: &dyn Shape, l: &PointLight, point: Point,
 
     let (diffuse, specular) = if light_dot_normal < 0.0 {
         (color(0.0, 0.0, 0.0), color(0.0, 0.0, 0.0))
+    } else if m.shading == Shading::Microfacet {
+        let (f_d, f_s) = microfacet_brdf(m, color_for_lightning, normal_v, eye_v, light_v);
+        let irradiance = l.intensity * (std::f32::consts::PI * light_dot_normal);
+        (f_d * irradiance, f_s * irradiance)
     } else {
         let diffuse = effective_color * m.diffuse * light_dot_normal;
         let reflect_v = reflect(-light_v, normal_v);
@@ -1484,15 +2553,11 @@ pub fn lightning(m: &Material, object: &dyn Shape, l: &PointLight, point: Point,
         (diffuse, specular)
     };
 
-    if in_shadow {
-        ambient
-    } else {
-        ambient + diffuse + specular
-    }
+    (ambient, diffuse, specular)
 }
 
 pub fn world() -> World {
-    World { objects: vec![], lights: vec![] }
+    World { objects: vec![], lights: vec![], background: Background::Solid(color(0.0, 0.0, 0.0)), environment_light: None, fog: None }
 }
 
 pub fn default_world() -> World {
@@ -1507,7 +2572,143 @@ pub fn default_world() -> World {
 
     let mut s2 = sphere();
     s2.set_transform(scaling(0.5, 0.5, 0.5));
-    World { objects: vec![s1, s2], lights: vec![light] }
+    World { objects: vec![s1, s2], lights: vec![light], background: Background::Solid(color(0.0, 0.0, 0.0)), environment_light: None, fog: None }
+}
+
+pub fn chapter8_world() -> World {
+    let mut floor = plane();
+    let mut mat = material();
+    mat.color = color(1.0, 0.9, 0.9);
+    mat.specular = 0.0;
+    floor.set_material(mat);
+
+    let mut middle = sphere();
+    middle.set_transform(translation(-0.5, 1.0, 0.5));
+    let mut mat = material();
+    let mut pattern = stripe_pattern(color(0.1, 1.0, 0.5), color(1.0, 1.0, 1.0));
+    pattern.set_transform(scaling(0.25, 0.25, 0.25));
+    mat.pattern = Some(Box::new(pattern));
+    mat.color = color(0.1, 1.0, 0.5);
+    mat.diffuse = 0.7;
+    mat.specular = 0.3;
+    middle.set_material(mat);
+
+    let mut world = world();
+    world.lights.push(point_light(point(-10.0, 3.0, -10.0), color(1.0, 1.0, 1.0)));
+    world.objects.push(floor);
+    world.objects.push(middle);
+    world.objects.push(chapter_right_sphere());
+    world.objects.push(chapter_left_sphere());
+    world
+}
+
+pub fn chapter11_world() -> World {
+    let mut world = chapter8_world();
+
+    let mut mat = material();
+    mat.transparency = 0.9;
+    mat.shininess = 0.9;
+    mat.refractive_index = 0.3;
+    mat.reflective = 0.5;
+    world.objects[1].set_material(mat);
+    world
+}
+
+/// Looks at the chapter scenes from where the book places its camera
+pub fn chapter_camera(hsize: i32, vsize: i32) -> Camera {
+    let mut camera = camera(hsize, vsize, std::f32::consts::PI / 3.0);
+    camera.transform = view_transformation(
+        point(0.0, 1.5, -5.0),
+        point(0.0, 1.0, 0.0),
+        vector(0.0, 1.0, 0.0));
+    camera
+}
+
+pub fn chapter4_clock(size: i32) -> Canvas {
+    let mut canvas = Canvas::new(size, size);
+    let radius = size as f32 * 3.0 / 16.0;
+    for i in 0..12 {
+        let hour = translation(size as f32 / 2.0, size as f32 / 2.0, 0.0)
+            * scaling(radius, radius, 1.0)
+            * rotation_z(i as f32 * std::f32::consts::PI / 6.0)
+            * point(0.0, 1.0, 0.0);
+        canvas.write_pixel(hour.x.round() as i32, hour.y.round() as i32, color(1.0, 1.0, 1.0));
+    }
+    canvas
+}
+
+pub fn chapter5_silhouette(size: i32) -> Canvas {
+    let shape = sphere();
+    chapter_wall(size, |r| hit(&shape.intersect(r)).map(|_| color(1.0, 0.0, 0.0)))
+}
+
+pub fn chapter6_sphere(size: i32) -> Canvas {
+    let mut shape = sphere();
+    shape.mut_material().color = color(1.0, 0.2, 1.0);
+    let light = point_light(point(-10.0, 10.0, -10.0), color(1.0, 1.0, 1.0));
+    chapter_wall(size, |r| hit(&shape.intersect(r)).map(|hit| {
+        let point = position(r, hit.t);
+        let normal = hit.object.normal_at(point);
+        lightning(hit.object.material(), hit.object, &light, point, r.direction, normal, false)
+    }))
+}
+
+fn chapter_wall(size: i32, shade: impl Fn(Ray) -> Option<Color>) -> Canvas {
+    let ray_origin = point(0.0, 0.0, -5.0);
+    let wall_z = 10.0;
+    let wall_size = 7.0;
+    let pixel_size = wall_size / size as f32;
+    let half = wall_size / 2.0;
+
+    let mut canvas = Canvas::new(size, size);
+    for y in 0..size {
+        let world_y = half - pixel_size * y as f32;
+        for x in 0..size {
+            let world_x = half - pixel_size * x as f32;
+            let r = ray(ray_origin, normalize(point(world_x, world_y, wall_z) - ray_origin));
+            if let Some(c) = shade(r) {
+                canvas.write_pixel(x, y, c);
+            }
+        }
+    }
+    canvas
+}
+
+pub fn chapter11_turntable() -> Animation {
+    let mut animation = animation();
+    animation.animate_camera(|t| view_transformation(
+        point(-5.0 * (t * std::f32::consts::PI / 2.0).sin(), 1.5, -5.0 * (t * std::f32::consts::PI / 2.0).cos()),
+        point(0.0, 1.0, 0.0),
+        vector(0.0, 1.0, 0.0)));
+    animation
+}
+
+fn chapter_right_sphere() -> Box<dyn Shape> {
+    let mut right = sphere();
+    right.set_transform(translation(1.5, 0.5, -0.5) * scaling(0.5, 0.5, 0.5));
+    let mut mat = material();
+    let mut pattern = stripe_pattern(color(0.5, 1.0, 0.1), color(1.0, 1.0, 1.0));
+    pattern.set_transform(rotation_z(std::f32::consts::PI / 4.0) * scaling(0.1, 0.1, 0.1));
+    mat.pattern = Some(Box::new(pattern));
+    mat.color = color(0.5, 1.0, 0.1);
+    mat.diffuse = 0.7;
+    mat.specular = 0.3;
+    right.set_material(mat);
+    right
+}
+
+fn chapter_left_sphere() -> Box<dyn Shape> {
+    let mut left = sphere();
+    left.set_transform(translation(-1.5, 0.33, -0.75) * scaling(0.33, 0.33, 0.33));
+    let mut mat = material();
+    let mut pattern = ring_gradient(color(1.0, 0.8, 0.1), color(0.8, 1.0, 0.1));
+    pattern.set_transform(scaling(0.1, 0.1, 0.1));
+    mat.pattern = Some(Box::new(pattern));
+    mat.color = color(1.0, 0.8, 0.1);
+    mat.diffuse = 0.7;
+    mat.specular = 0.3;
+    left.set_material(mat);
+    left
 }
 
 pub fn stripe_pattern(a: Color, b: Color) -> StripePattern {
@@ -1551,92 +2752,558 @@ pub fn set_pattern_transformation(pattern: &mut StripePattern, transform: Matrix
 }
 
 pub fn shade_hit(w: &World, c: &Computation, remaining: u8) -> Color {
-    let is_shadowed = is_shadowed(w, c.over_point);
-    let surface = lightning(c.object.material(), c.object, &w.lights[0], c.over_point, c.eye_v, c.normal_v, is_shadowed);
+    shade_hit_within(w, c, depth_budget(remaining))
+}
+
+fn depth_budget(remaining: u8) -> RayBudget {
+    RayBudget { reflections: remaining, refractions: remaining, depth: Some(remaining), contribution: 1.0, min_contribution: 0.0 }
+}
 
-    let reflected = reflected_color(w, c, remaining);
-    let refracted = refracted_color(w, c, remaining);
+fn shade_hit_within(w: &World, c: &Computation, budget: RayBudget) -> Color {
+    if c.object.is_volume() {
+        let scattered = scattered_color(w, c);
+        record_contributions(scattered, color(0.0, 0.0, 0.0), color(0.0, 0.0, 0.0));
+        return scattered;
+    }
+    let mut surface = match w.lights.first() {
+        | None => color(0.0, 0.0, 0.0),
+        | Some(light) => {
+            let v = light.position - c.over_point;
+            let visible = shadow_attenuation(w, c.over_point, normalize(v), magnitude(v), c.time);
+            let m = c.object.material();
+            lightning_of(m, surface_color_at(m, c.object, c.over_point, c.time), light, c.over_point, c.eye_v, c.normal_v, visible)
+        }
+    };
+    if let Some(light) = &w.environment_light {
+        surface = surface + environment_lighting(w, c, light);
+    }
+    surface = surface + emitted(c.object.material());
 
     let material =c.object.material();
-    if material.reflective > 0.0 && material.transparency > 0.0 {
-        let reflectance = schlick(c);
-        surface + reflected * reflectance + refracted * (1.0f32 - reflectance)
+    let (reflected, refracted) = if material.transparency > 0.0 {
+        let reflective = material.reflective.clamp(0.0, 1.0);
+        let reflectance = reflective + (1.0 - reflective) * fresnel(c);
+        let reflected = reflected_color_weighted(w, c, budget, reflectance);
+        (reflected, refracted_color_within(w, c, budget) * (1.0 - reflectance))
     }
     else {
-        surface + reflected + refracted
+        (reflected_color_within(w, c, budget), refracted_color_within(w, c, budget))
+    };
+    record_contributions(surface, reflected, refracted);
+
+    surface + reflected + refracted
+}
+
+/// Isotropic scattering, every light reaches the point regardless of direction
+pub fn scattered_color(w: &World, c: &Computation) -> Color {
+    let m = c.object.material();
+    let base = surface_color_at(m, c.object, c.point, c.time);
+    let mut result = emitted(m);
+    for light in &w.lights {
+        let effective_color = base * light.intensity;
+        result = result + effective_color * m.ambient;
+        let v = light.position - c.over_point;
+        let visible = shadow_attenuation(w, c.over_point, normalize(v), magnitude(v), c.time);
+        result = result + effective_color * m.diffuse * visible;
     }
+    result
 }
 
 pub fn reflected_color(w: &World, c: &Computation, remaining: u8) -> Color {
-    if remaining == 0 {
-        return color(0.0, 0.0, 0.0);
-    }
-    if c.object.material().reflective == 0.0 {
+    reflected_color_within(w, c, depth_budget(remaining))
+}
+
+fn reflected_color_within(w: &World, c: &Computation, budget: RayBudget) -> Color {
+    reflected_color_weighted(w, c, budget, c.object.material().reflective)
+}
+
+fn reflected_color_weighted(w: &World, c: &Computation, budget: RayBudget, reflective: f32) -> Color {
+    if reflective == 0.0 {
         return color(0.0, 0.0, 0.0);
     }
-    let reflect_ray = ray(c.over_point, c.reflect_v);
-    let color = color_at(w, reflect_ray, remaining - 1);
+    let budget = match budget.reflect(reflective) {
+        | None => return color(0.0, 0.0, 0.0),
+        | Some(budget) => budget
+    };
+    let reflect_ray = secondary_ray(c, c.over_point, c.reflect_v, RayKind::Reflection);
+    let color = color_at_within(w, reflect_ray, budget);
 
-    color * c.object.material().reflective
+    color * reflective
 }
 
 pub fn refracted_color(w: &World, c: &Computation, remaining: u8) -> Color {
-    if remaining == 0 {
-        return color(0.0, 0.0, 0.0);
-    }
+    refracted_color_within(w, c, depth_budget(remaining))
+}
+
+fn refracted_color_within(w: &World, c: &Computation, budget: RayBudget) -> Color {
     if c.object.material().transparency == 0.0 {
         return color(0.0, 0.0, 0.0);
     }
-    let n_ratio = c.n1 / c.n2;
+    let budget = match budget.refract(c.object.material().transparency) {
+        | None => return color(0.0, 0.0, 0.0),
+        | Some(budget) => budget
+    };
+    if c.channel.is_some() || (c.n1_dispersion == 0.0 && c.n2_dispersion == 0.0) {
+        let direction = match refraction_direction(c) {
+            | None => return color(0.0, 0.0, 0.0),
+            | Some(direction) => direction
+        };
 
-    let cos_i = dot(c.eye_v, c.normal_v);
+        let refracted_ray = secondary_ray(c, c.under_point, direction, RayKind::Refraction);
 
-    let sin2_t = n_ratio.powf(2.0) * (1.0 - cos_i.powf(2.0));
+        return color_at_within(w, refracted_ray, budget) * c.object.material().transparency;
+    }
 
-    if sin2_t > 1.0 {
-        return color(0.0, 0.0, 0.0);
+    let mut channels = [0.0f32; 3];
+    for (i, channel) in channels.iter_mut().enumerate() {
+        let comps = dispersed(c, i);
+        if let Some(direction) = refraction_direction(&comps) {
+            let refracted = color_at_within(w, secondary_ray(&comps, c.under_point, direction, RayKind::Refraction), budget);
+            *channel = [refracted.red, refracted.green, refracted.blue][i];
+        }
     }
 
-    let cos_t = (1.0 - sin2_t).sqrt();
+    color(channels[0], channels[1], channels[2]) * c.object.material().transparency
+}
 
-    let direction = c.normal_v * (n_ratio * cos_i - cos_t) - c.eye_v * n_ratio;
+fn dispersed<'a>(c: &Computation<'a>, channel: usize) -> Computation<'a> {
+    let mut comps = *c;
+    comps.n1 = refractive_index_at(c.n1, c.n1_dispersion, RGB_WAVELENGTHS[channel]);
+    comps.n2 = refractive_index_at(c.n2, c.n2_dispersion, RGB_WAVELENGTHS[channel]);
+    comps.channel = Some(channel);
+    comps
+}
 
-    let refracted_ray = ray(c.under_point, direction);
+fn secondary_ray(c: &Computation, origin: Point, direction: Vector, kind: RayKind) -> Ray {
+    Ray { channel: c.channel, ..ray_of_kind(origin, direction, c.time, kind) }
+}
 
-    color_at(w, refracted_ray, remaining - 1) * c.object.material().transparency
+/// Cauchy's equation, anchored so the green wavelength keeps the base index
+pub fn refractive_index_at(n: f32, dispersion: f32, wavelength: f32) -> f32 {
+    n + dispersion * (1.0 / wavelength.powf(2.0) - 1.0 / RGB_WAVELENGTHS[1].powf(2.0))
 }
 
-pub fn color_at<'a>(w: &'a World, r: Ray, remaining: u8) -> Color {
-    let intersections: Vec<Intersection<'a>> = intersect_world(w, r);
+fn refraction_direction(c: &Computation) -> Option<Vector> {
+    let n_ratio = c.n1 / c.n2;
 
-    let hit= hit(&intersections);
-    match hit
-    {
-        | None => color(0.0, 0.0, 0.0),
-        | Some(i) => {
-            let comp = prepare_computations(i, r, &intersections);
-            shade_hit(w, &comp, remaining)
-        }
-    }
-}
+    let cos_i = dot(c.eye_v, c.normal_v);
 
-pub fn schlick(c: &Computation) -> f32 {
+    let sin2_t = n_ratio.powf(2.0) * (1.0 - cos_i.powf(2.0));
 
-    let mut cos = dot(c.eye_v, c.normal_v);
+    if sin2_t > 1.0 {
+        return None;
+    }
 
-    if c.n1 > c.n2 {
-        let n = c.n1 / c.n2;
-        let sin2_t = n.powf(2.0) * (1.0 - cos.powf(2.0));
-        if sin2_t > 1.0 {
-            return 1.0;
-        }
+    let cos_t = (1.0 - sin2_t).sqrt();
 
-        let cos_t = (1.0 - sin2_t.powf(2.0)).sqrt();
+    Some(c.normal_v * (n_ratio * cos_i - cos_t) - c.eye_v * n_ratio)
+}
 
-        cos = cos_t;
-    }
-    let r0 = ((c.n1 - c.n2) / (c.n1 + c.n2)).powf(2.0);
-    r0 + (1.0f32 - r0) * (1.0 - cos).powf(5.0)
+pub fn path_trace(w: &World, r: Ray, max_depth: u8) -> Color {
+    path_trace_within(w, r, depth_budget(max_depth))
+}
+
+fn path_trace_within(w: &World, r: Ray, budget: RayBudget) -> Color {
+    let mut radiance = color(0.0, 0.0, 0.0);
+    let mut throughput = color(1.0, 1.0, 1.0);
+    let mut r = r;
+    let mut budget = budget;
+    let recording = is_recording();
+    let mut bounces = vec![];
+
+    for bounce in 0.. {
+        record_ray_start(r);
+        let mut surface = color(0.0, 0.0, 0.0);
+        let mut weight = color(1.0, 1.0, 1.0);
+        let intersections = intersect_world(w, r);
+        let c = match scattering_hit(&intersections, r) {
+            | None => {
+                surface = fogged(w, background_color(&w.background, r.direction), f32::INFINITY);
+                radiance = radiance + throughput * surface;
+                if recording {
+                    bounces.push((surface, weight, None));
+                }
+                break;
+            }
+            | Some(i) => prepare_computations(i, r, &intersections)
+        };
+        record_hit(&c);
+        let m = c.object.material();
+        let distance = c.t * magnitude(r.direction);
+        if let Some(fog) = w.fog {
+            let t = fog_transmittance(w, distance);
+            surface = surface + fog.color * (1.0 - t);
+            weight = weight * t;
+        }
+        if c.inside {
+            weight = weight * transmittance(m, distance);
+        }
+        surface = surface + weight * emitted(m);
+
+        let reflective = m.reflective.clamp(0.0, 1.0);
+        let transparency = m.transparency.clamp(0.0, 1.0 - reflective);
+        let choice = random_f32();
+        let direction = if c.object.is_volume() {
+            let base = surface_color_at(m, c.object, c.point, c.time);
+            for light in &w.lights {
+                let v = light.position - c.over_point;
+                let visible = shadow_attenuation(w, c.over_point, normalize(v), magnitude(v), c.time);
+                surface = surface + weight * base * light.intensity * m.diffuse * visible;
+            }
+            weight = weight * base;
+            secondary_ray(&c, c.point, sample_sphere(), RayKind::Reflection)
+        } else if choice < reflective {
+            secondary_ray(&c, c.over_point, c.reflect_v, RayKind::Reflection)
+        } else if choice < reflective + transparency {
+            let c = if c.channel.is_none() && (c.n1_dispersion != 0.0 || c.n2_dispersion != 0.0) {
+                let channel = ((random_f32() * 3.0) as usize).min(2);
+                weight = weight * [color(3.0, 0.0, 0.0), color(0.0, 3.0, 0.0), color(0.0, 0.0, 3.0)][channel];
+                dispersed(&c, channel)
+            } else {
+                c
+            };
+            match refraction_direction(&c) {
+                | Some(direction) if random_f32() >= fresnel(&c) =>
+                    secondary_ray(&c, c.under_point, direction, RayKind::Refraction),
+                | _ => secondary_ray(&c, c.over_point, c.reflect_v, RayKind::Reflection)
+            }
+        } else {
+            let base = surface_color_at(m, c.object, c.over_point, c.time);
+            for light in &w.lights {
+                let v = light.position - c.over_point;
+                let visible = shadow_attenuation(w, c.over_point, normalize(v), magnitude(v), c.time);
+                let (_, diffuse, specular) = lightning_terms(m, base, light, c.over_point, c.eye_v, c.normal_v);
+                surface = surface + weight * (diffuse + specular) * visible;
+            }
+            let bounce = sample_hemisphere_cosine(c.normal_v);
+            weight = weight * match m.shading {
+                | Shading::Phong => base * m.diffuse,
+                | Shading::Microfacet => {
+                    let (f_d, f_s) = microfacet_brdf(m, base, c.normal_v, c.eye_v, bounce);
+                    (f_d + f_s) * std::f32::consts::PI
+                }
+            };
+            secondary_ray(&c, c.over_point, bounce, RayKind::Reflection)
+        };
+        radiance = radiance + throughput * surface;
+
+        let next = match direction.kind {
+            | RayKind::Refraction => budget.refract(1.0),
+            | _ => budget.reflect(1.0)
+        };
+        let survives = match next {
+            | None => false,
+            | Some(_) if bounce < 3 => true,
+            | Some(_) => {
+                let carried = throughput * weight;
+                let survival = carried.red.max(carried.green).max(carried.blue).clamp(0.05, 1.0);
+                weight = weight * (1.0 / survival);
+                random_f32() < survival
+            }
+        };
+        if recording {
+            bounces.push((surface, weight, survives.then_some(direction.kind)));
+        }
+        match next {
+            | Some(next) if survives => budget = next,
+            | _ => break
+        }
+        throughput = throughput * weight;
+        r = direction;
+    }
+    let mut carried = color(0.0, 0.0, 0.0);
+    for (surface, weight, next) in bounces.into_iter().rev() {
+        let continued = if next.is_some() { weight * carried } else { color(0.0, 0.0, 0.0) };
+        match next {
+            | Some(RayKind::Refraction) => record_contributions(surface, color(0.0, 0.0, 0.0), continued),
+            | _ => record_contributions(surface, continued, color(0.0, 0.0, 0.0))
+        }
+        carried = surface + continued;
+        record_ray_end(carried);
+    }
+    radiance
+}
+
+pub fn integrate(w: &World, r: Ray, integrator: Integrator) -> Color {
+    integrate_within(w, r, integrator, &render_settings())
+}
+
+fn integrate_within(w: &World, r: Ray, integrator: Integrator, settings: &RenderSettings) -> Color {
+    match integrator {
+        | Integrator::Whitted => color_at_within(w, r, settings.ray_budget()),
+        | Integrator::PathTracer => path_trace_within(w, r, depth_budget(settings.max_path_depth))
+    }
+}
+
+pub fn color_at(w: &World, r: Ray, remaining: u8) -> Color {
+    color_at_within(w, r, depth_budget(remaining))
+}
+
+pub fn color_at_within(w: &World, r: Ray, budget: RayBudget) -> Color {
+    record_ray_start(r);
+    let color = trace_ray(w, r, budget);
+    record_ray_end(color);
+    color
+}
+
+fn trace_ray<'a>(w: &'a World, r: Ray, budget: RayBudget) -> Color {
+    let intersections: Vec<Intersection<'a>> = intersect_world(w, r);
+
+    let hit= scattering_hit(&intersections, r);
+    match hit
+    {
+        | None => fogged(w, background_color(&w.background, r.direction), f32::INFINITY),
+        | Some(i) => {
+            let comp = prepare_computations(i, r, &intersections);
+            record_hit(&comp);
+            let color = shade_hit_within(w, &comp, budget);
+            let distance = comp.t * magnitude(r.direction);
+            if comp.inside {
+                fogged(w, color * transmittance(comp.object.material(), distance), distance)
+            } else {
+                fogged(w, color, distance)
+            }
+        }
+    }
+}
+
+fn scattering_hit<'a>(xs: &[Intersection<'a>], r: Ray) -> Option<Intersection<'a>> {
+    let surfaces = xs.iter().filter(|i| !i.object.is_volume()).copied().collect::<Vec<_>>();
+    let mut nearest = hit(&surfaces);
+    let length = magnitude(r.direction);
+    for (object, enter, exit) in spans(xs) {
+        let Some(medium) = object.as_any().downcast_ref::<ConstantMedium>() else {
+            continue;
+        };
+        let enter = enter.max(0.0);
+        let exit = nearest.map_or(exit, |n| exit.min(n.t));
+        if exit <= enter {
+            continue;
+        }
+        let t = enter - (1.0 - random_f32()).ln() / (medium.density * length);
+        if t < exit {
+            nearest = Some(intersection(t, object));
+        }
+    }
+    nearest
+}
+
+fn spans<'a>(xs: &[Intersection<'a>]) -> Vec<(&'a dyn Shape, f32, f32)> {
+    let mut spans = vec![];
+    let mut open: Vec<(Uuid, f32)> = vec![];
+    for i in xs {
+        match open.iter().position(|(id, _)| *id == i.object.id()) {
+            | Some(k) => spans.push((i.object, open.remove(k).1, i.t)),
+            | None => open.push((i.object.id(), i.t))
+        }
+    }
+    spans
+}
+
+pub fn fog_transmittance(w: &World, distance: f32) -> f32 {
+    match w.fog {
+        | Some(fog) if fog.density > 0.0 => (-fog.density * distance).exp(),
+        | _ => 1.0
+    }
+}
+
+fn fogged(w: &World, c: Color, distance: f32) -> Color {
+    match w.fog {
+        | Some(fog) => {
+            let t = fog_transmittance(w, distance);
+            c * t + fog.color * (1.0 - t)
+        }
+        | None => c
+    }
+}
+
+pub fn transmittance(m: &Material, distance: f32) -> Color {
+    if m.absorption_density <= 0.0 {
+        return color(1.0, 1.0, 1.0);
+    }
+    let d = distance * m.absorption_density;
+    color(m.absorption.red.powf(d), m.absorption.green.powf(d), m.absorption.blue.powf(d))
+}
+
+pub fn environment_map(image: Canvas) -> Result<EnvironmentMap, String> {
+    if image.width <= 0 || image.height <= 0 {
+        return Err(format!("Environment map must not be empty, got {}x{}", image.width, image.height));
+    }
+    let mut conditional_cdf = Vec::with_capacity(image.pixels.len());
+    let mut marginal_cdf = Vec::with_capacity(image.height as usize);
+    let mut total = 0.0;
+    for y in 0..image.height {
+        let sin_theta = (std::f32::consts::PI * (y as f32 + 0.5) / image.height as f32).sin();
+        let mut row_total = 0.0;
+        for x in 0..image.width {
+            row_total += luminance(image.pixel_at(x, y)).max(0.0) * sin_theta;
+            conditional_cdf.push(row_total);
+        }
+        total += row_total;
+        marginal_cdf.push(total);
+    }
+    Ok(EnvironmentMap { image, intensity: 1.0, marginal_cdf, conditional_cdf })
+}
+
+pub fn environment_light(samples: u32) -> EnvironmentLight {
+    EnvironmentLight { samples, intensity: 1.0 }
+}
+
+pub fn luminance(c: Color) -> f32 {
+    0.2126 * c.red + 0.7152 * c.green + 0.0722 * c.blue
+}
+
+fn direction_from_uv(u: f32, v: f32) -> Vector {
+    let phi = (u - 0.5) * 2.0 * std::f32::consts::PI;
+    let theta = v * std::f32::consts::PI;
+    vector(theta.sin() * phi.sin(), theta.cos(), -theta.sin() * phi.cos())
+}
+
+fn sample_cdf(cdf: &[f32], value: f32) -> usize {
+    let target = value * cdf[cdf.len() - 1];
+    cdf.partition_point(|c| *c <= target).min(cdf.len() - 1)
+}
+
+pub fn sample_environment(map: &EnvironmentMap) -> (Vector, Color, f32) {
+    let (width, height) = (map.image.width as usize, map.image.height as usize);
+    let total = map.marginal_cdf[height - 1];
+    if total <= 0.0 {
+        let direction = sample_sphere();
+        return (direction, color(0.0, 0.0, 0.0), 1.0 / (4.0 * std::f32::consts::PI));
+    }
+
+    let y = sample_cdf(&map.marginal_cdf, random_f32());
+    let x = sample_cdf(&map.conditional_cdf[y * width..(y + 1) * width], random_f32());
+    let u = (x as f32 + random_f32()) / width as f32;
+    let v = (y as f32 + random_f32()) / height as f32;
+    let direction = direction_from_uv(u, v);
+
+    let row_start = if x == 0 { 0.0 } else { map.conditional_cdf[y * width + x - 1] };
+    let texel_weight = map.conditional_cdf[y * width + x] - row_start;
+    let sin_theta = (v * std::f32::consts::PI).sin().max(EPS);
+    let pdf = texel_weight / total * (width * height) as f32 /
+        (2.0 * std::f32::consts::PI * std::f32::consts::PI * sin_theta);
+
+    (direction, environment_color(map, direction), pdf)
+}
+
+fn sample_sphere() -> Vector {
+    let y = 1.0 - 2.0 * random_f32();
+    let r = (1.0 - y * y).max(0.0).sqrt();
+    let phi = 2.0 * std::f32::consts::PI * random_f32();
+    vector(r * phi.cos(), y, r * phi.sin())
+}
+
+pub fn sample_hemisphere_cosine(normal: Vector) -> Vector {
+    let r = random_f32().sqrt();
+    let phi = 2.0 * std::f32::consts::PI * random_f32();
+    let (x, y) = (r * phi.cos(), r * phi.sin());
+    let z = (1.0 - x * x - y * y).max(0.0).sqrt();
+
+    let helper = if normal.x.abs() > 0.9 { vector(0.0, 1.0, 0.0) } else { vector(1.0, 0.0, 0.0) };
+    let tangent = normalize(cross(helper, normal));
+    let bitangent = cross(normal, tangent);
+    normalize(tangent * x + bitangent * y + normal * z)
+}
+
+pub fn sample_background(b: &Background, normal: Vector) -> (Vector, Color, f32) {
+    match b {
+        | Background::Environment(map) => sample_environment(map),
+        | _ => {
+            let direction = sample_hemisphere_cosine(normal);
+            let pdf = dot(direction, normal).max(EPS) / std::f32::consts::PI;
+            (direction, background_color(b, direction), pdf)
+        }
+    }
+}
+
+pub fn environment_lighting(w: &World, c: &Computation, light: &EnvironmentLight) -> Color {
+    let m = c.object.material();
+    let albedo = surface_color_at(m, c.object, c.over_point, c.time) * m.diffuse;
+    let samples = light.samples.max(1);
+
+    let mut irradiance = color(0.0, 0.0, 0.0);
+    for _ in 0..samples {
+        let (direction, radiance, pdf) = sample_background(&w.background, c.normal_v);
+        let cos = dot(direction, c.normal_v);
+        if cos <= 0.0 || pdf <= 0.0 || is_occluded(w, c.over_point, direction, f32::INFINITY, c.time) {
+            continue;
+        }
+        irradiance = irradiance + radiance * (cos / pdf);
+    }
+
+    albedo * irradiance * (light.intensity / (std::f32::consts::PI * samples as f32))
+}
+
+pub fn background_color(b: &Background, direction: Vector) -> Color {
+    let direction = normalize(direction);
+    match b {
+        | Background::Solid(c) => *c,
+        | Background::Gradient { top, bottom } => {
+            let t = 0.5 * (direction.y + 1.0);
+            *bottom * (1.0 - t) + *top * t
+        }
+        | Background::Environment(map) => environment_color(map, direction)
+    }
+}
+
+pub fn environment_color(map: &EnvironmentMap, direction: Vector) -> Color {
+    let direction = normalize(direction);
+    let u = 0.5 + direction.x.atan2(-direction.z) / (2.0 * std::f32::consts::PI);
+    let v = direction.y.clamp(-1.0, 1.0).acos() / std::f32::consts::PI;
+
+    let image = &map.image;
+    let x = u * image.width as f32 - 0.5;
+    let y = (v * image.height as f32 - 0.5).clamp(0.0, (image.height - 1) as f32);
+    let (x0, y0) = (x.floor(), y.floor());
+    let (fx, fy) = (x - x0, y - y0);
+    let column = |x: f32| (x as i32).rem_euclid(image.width);
+    let row = |y: f32| (y as i32).min(image.height - 1);
+
+    let top = image.pixel_at(column(x0), row(y0)) * (1.0 - fx) + image.pixel_at(column(x0 + 1.0), row(y0)) * fx;
+    let bottom = image.pixel_at(column(x0), row(y0 + 1.0)) * (1.0 - fx) + image.pixel_at(column(x0 + 1.0), row(y0 + 1.0)) * fx;
+
+    (top * (1.0 - fy) + bottom * fy) * map.intensity
+}
+
+pub fn schlick(c: &Computation) -> f32 {
+
+    let mut cos = dot(c.eye_v, c.normal_v);
+
+    if c.n1 > c.n2 {
+        let n = c.n1 / c.n2;
+        let sin2_t = n.powf(2.0) * (1.0 - cos.powf(2.0));
+        if sin2_t > 1.0 {
+            return 1.0;
+        }
+
+        let cos_t = (1.0 - sin2_t).sqrt();
+
+        cos = cos_t;
+    }
+    let r0 = ((c.n1 - c.n2) / (c.n1 + c.n2)).powf(2.0);
+    r0 + (1.0f32 - r0) * (1.0 - cos).powf(5.0)
+}
+
+pub fn fresnel_exact(c: &Computation) -> f32 {
+    let cos_i = dot(c.eye_v, c.normal_v);
+    let n = c.n1 / c.n2;
+    let sin2_t = n.powf(2.0) * (1.0 - cos_i.powf(2.0));
+    if sin2_t > 1.0 {
+        return 1.0;
+    }
+    let cos_t = (1.0 - sin2_t).sqrt();
+
+    let r_s = (c.n1 * cos_i - c.n2 * cos_t) / (c.n1 * cos_i + c.n2 * cos_t);
+    let r_p = (c.n2 * cos_i - c.n1 * cos_t) / (c.n2 * cos_i + c.n1 * cos_t);
+    (r_s.powf(2.0) + r_p.powf(2.0)) / 2.0
+}
+
+pub fn fresnel(c: &Computation) -> f32 {
+    match c.object.material().fresnel {
+        | Fresnel::Schlick => schlick(c),
+        | Fresnel::Exact => fresnel_exact(c)
+    }
 }
 
 fn check_axis(origin: f32, direction: f32) -> (f32, f32) {
@@ -1682,12 +3349,33 @@ pub fn camera(hsize: i32, vsize: i32, field_of_view: f32) -> Camera {
         half_width,
         half_height,
         transform: Matrix::identity4x4(),
-        pixel_size: half_width * 2.0 / (hsize as f32) }
+        pixel_size: half_width * 2.0 / (hsize as f32),
+        shutter_open: 0.0,
+        shutter_close: 0.0 }
 }
 
 pub fn ray_for_pixel(c: &Camera, px: i32, py: i32) -> Ray {
-    let x_offset = (px as f32 + 0.5) * c.pixel_size;
-    let y_offset = (py as f32 + 0.5) * c.pixel_size;
+    ray_through(c, px as f32 + 0.5, py as f32 + 0.5, c.shutter_open)
+}
+
+pub fn ray_for_pixel_sample(c: &Camera, px: i32, py: i32, sample: u32) -> Ray {
+    seed_random(((py as u64) << 40) ^ ((px as u64) << 20) ^ sample as u64);
+    let (dx, dy) = if sample == 0 {
+        (0.5, 0.5)
+    } else {
+        (random_f32(), random_f32())
+    };
+    let time = if c.shutter_close > c.shutter_open {
+        c.shutter_open + (c.shutter_close - c.shutter_open) * random_f32()
+    } else {
+        c.shutter_open
+    };
+    ray_through(c, px as f32 + dx, py as f32 + dy, time)
+}
+
+fn ray_through(c: &Camera, x: f32, y: f32, time: f32) -> Ray {
+    let x_offset = x * c.pixel_size;
+    let y_offset = y * c.pixel_size;
 
     let world_x = c.half_width - x_offset;
     let world_y = c.half_height - y_offset;
@@ -1695,34 +3383,423 @@ pub fn ray_for_pixel(c: &Camera, px: i32, py: i32) -> Ray {
     let pixel = inverse(&c.transform) * point(world_x, world_y, -1.0);
     let origin = inverse(&c.transform) * point(0.0, 0.0, 0.0);
     let direction = normalize(pixel - origin);
-    Ray { origin, direction }
+    Ray { origin, direction, time, kind: RayKind::Camera, channel: None }
 }
 
 pub fn render(camera: &Camera, world: &World) -> Canvas {
-    let mut c = Canvas::new(camera.hsize, camera.vsize);
-    for y in 0..camera.vsize {
-        for x in 0..camera.hsize {
-            let ray = ray_for_pixel(camera, x, y);
-            let color = color_at(world, ray, DEFAULT_REFLECTION_NUMBER);
-            c.write_pixel(x, y, color)
+    render_with_integrator(camera, world, Integrator::Whitted)
+}
+
+pub fn render_with_integrator(camera: &Camera, world: &World, integrator: Integrator) -> Canvas {
+    let mut settings = render_settings();
+    settings.integrator = integrator;
+    render_with_settings(camera, world, &settings)
+}
+
+pub fn render_with_settings(camera: &Camera, world: &World, settings: &RenderSettings) -> Canvas {
+    render_with_callback(camera, world, settings, |_, _| true)
+}
+
+/// Renders the image in linear color, so it can be denoised before `tone_map` is applied
+pub fn render_with_aovs(camera: &Camera, world: &World, settings: &RenderSettings) -> (Canvas, Aovs) {
+    let (image, aovs) = render_samples(camera, world, settings, 0..settings.samples.max(1), true, |_, _| true);
+    (image, aovs.unwrap())
+}
+
+fn aov_sample(camera: &Camera, w: &World, r: Ray) -> [Color; 5] {
+    let intersections = intersect_world(w, r);
+    match hit(&intersections) {
+        | None => [color(0.0, 0.0, 0.0); 5],
+        | Some(i) => {
+            let c = prepare_computations(i, r, &intersections);
+            let depth = -(camera.transform.clone() * c.point).z;
+            [
+                color(depth, depth, depth),
+                color(c.normal_v.x, c.normal_v.y, c.normal_v.z),
+                surface_color_at(c.object.material(), c.object, c.point, c.time),
+                object_id_color(c.object.id()),
+                light_visibility(w, &c),
+            ]
+        }
+    }
+}
+
+fn write_aovs(aovs: &mut Aovs, x: i32, y: i32, values: [Color; 5]) {
+    let [depth, normal, albedo, object_id, shadow] = values;
+    aovs.depth.write_pixel(x, y, depth);
+    aovs.normal.write_pixel(x, y, normal);
+    aovs.albedo.write_pixel(x, y, albedo);
+    aovs.object_id.write_pixel(x, y, object_id);
+    aovs.shadow.write_pixel(x, y, shadow);
+}
+
+pub fn object_id_color(id: Uuid) -> Color {
+    let bytes = id.as_bytes();
+    color(bytes[0] as f32 / 255.0, bytes[1] as f32 / 255.0, bytes[2] as f32 / 255.0)
+}
+
+fn light_visibility(w: &World, c: &Computation) -> Color {
+    if w.lights.is_empty() {
+        return color(1.0, 1.0, 1.0);
+    }
+    let mut visible = color(0.0, 0.0, 0.0);
+    for light in &w.lights {
+        let v = light.position - c.over_point;
+        visible = visible + shadow_attenuation(w, c.over_point, normalize(v), magnitude(v), c.time);
+    }
+    visible * (1.0 / w.lights.len() as f32)
+}
+
+pub fn render_with_callback<F>(camera: &Camera, world: &World, settings: &RenderSettings, on_tile: F) -> Canvas
+    where F: FnMut(&RenderProgress, &Canvas) -> bool {
+    let (image, _) = render_samples(camera, world, settings, 0..settings.samples.max(1), false, on_tile);
+    tone_map(&image, &settings.tone_mapping)
+}
+
+/// Pass k traces sample k of every pixel, so n passes match a render with n samples
+pub fn render_progressive<F>(camera: &Camera, world: &World, settings: &RenderSettings,
+                             time_limit: Option<Duration>, mut on_pass: F) -> Canvas
+    where F: FnMut(u32, &Canvas) -> bool {
+    let start = Instant::now();
+    let (mut canvas, _) = output_canvas(camera, settings);
+    let mut sums = vec![color(0.0, 0.0, 0.0); canvas.pixels.len()];
+    for pass in 0..settings.samples.max(1) {
+        let (samples, _) = render_samples(camera, world, settings, pass..pass + 1, false, |_, _| true);
+        let scale = 1.0 / (pass + 1) as f32;
+        for (i, c) in samples.pixels.into_iter().enumerate() {
+            sums[i] = sums[i] + c;
+            canvas.pixels[i] = sums[i] * scale;
+        }
+        let image = tone_map(&canvas, &settings.tone_mapping);
+        if !on_pass(pass + 1, &image) || time_limit.is_some_and(|limit| start.elapsed() >= limit) {
+            return image;
+        }
+    }
+    tone_map(&canvas, &settings.tone_mapping)
+}
+
+fn render_samples<F>(camera: &Camera, world: &World, settings: &RenderSettings, samples: Range<u32>,
+                     with_aovs: bool, mut on_tile: F) -> (Canvas, Option<Aovs>)
+    where F: FnMut(&RenderProgress, &Canvas) -> bool {
+    let region = render_region(camera, settings);
+    let tiles: Vec<Tile> = tiles(region.width, region.height, settings.tile_size).into_iter()
+        .map(|t| Tile { x: t.x + region.x, y: t.y + region.y, ..t })
+        .collect();
+    let threads = match settings.threads {
+        | 0 => thread::available_parallelism().map_or(1, |n| n.get()),
+        | n => n
+    }.clamp(1, tiles.len().max(1));
+    let next = AtomicUsize::new(0);
+    let cancelled = AtomicBool::new(false);
+    let (mut canvas, (origin_x, origin_y)) = output_canvas(camera, settings);
+    let mut display = canvas.clone();
+    let mut aovs = with_aovs.then(|| Aovs {
+        depth: Canvas::new(canvas.width, canvas.height),
+        normal: Canvas::new(canvas.width, canvas.height),
+        albedo: Canvas::new(canvas.width, canvas.height),
+        object_id: Canvas::new(canvas.width, canvas.height),
+        shadow: Canvas::new(canvas.width, canvas.height),
+    });
+
+    thread::scope(|scope| {
+        let (sender, receiver) = mpsc::sync_channel(0);
+        for _ in 0..threads {
+            let sender = sender.clone();
+            let (tiles, next, cancelled, samples) = (&tiles, &next, &cancelled, &samples);
+            scope.spawn(move || {
+                while !cancelled.load(Relaxed) {
+                    let tile = match tiles.get(next.fetch_add(1, Relaxed)) {
+                        | None => break,
+                        | Some(tile) => *tile
+                    };
+                    if sender.send((tile, render_tile(camera, world, tile, settings, samples.clone(), with_aovs))).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+        drop(sender);
+
+        for (completed, (tile, pixels)) in receiver.iter().enumerate() {
+            for (i, (c, values)) in pixels.into_iter().enumerate() {
+                let (x, y) = (tile.x - origin_x + i as i32 % tile.width, tile.y - origin_y + i as i32 / tile.width);
+                canvas.write_pixel(x, y, c);
+                display.write_pixel(x, y, tone_map_color(c, &settings.tone_mapping));
+                if let (Some(aovs), Some(values)) = (aovs.as_mut(), values) {
+                    write_aovs(aovs, x, y, values);
+                }
+            }
+            let progress = RenderProgress { tile, completed: completed + 1, total: tiles.len() };
+            if !cancelled.load(Relaxed) && !on_tile(&progress, &display) {
+                cancelled.store(true, Relaxed);
+            }
+        }
+    });
+    (canvas, aovs)
+}
+
+/// Traces one pixel on this thread, returning a ray tree for each sample
+pub fn trace_pixel(camera: &Camera, world: &World, settings: &RenderSettings, x: i32, y: i32) -> Vec<RayNode> {
+    RECORDING.with(|r| r.set(true));
+    pixel_color(camera, world, x, y, settings, 0..settings.samples.max(1), false);
+    RECORDING.with(|r| r.set(false));
+    RAY_RECORDER.with(|r| std::mem::take(&mut r.borrow_mut().roots))
+}
+
+fn is_recording() -> bool {
+    RECORDING.with(Cell::get)
+}
+
+fn with_recorder(f: impl FnOnce(&mut RayRecorder)) {
+    if is_recording() {
+        RAY_RECORDER.with(|recorder| f(&mut recorder.borrow_mut()));
+    }
+}
+
+fn record_ray_start(r: Ray) {
+    with_recorder(|recorder| {
+        let black = color(0.0, 0.0, 0.0);
+        recorder.open.push(RayNode {
+            ray: r, hit: None, color: black, surface: black, reflected: black, refracted: black, children: vec![]
+        });
+    });
+}
+
+fn record_hit(c: &Computation) {
+    with_recorder(|recorder| {
+        if let Some(node) = recorder.open.last_mut() {
+            node.hit = Some(RayHit {
+                object: c.object.id(),
+                t: c.t,
+                point: c.point,
+                normal_v: c.normal_v,
+                inside: c.inside,
+                n1: c.n1,
+                n2: c.n2,
+            });
+        }
+    });
+}
+
+fn record_contributions(surface: Color, reflected: Color, refracted: Color) {
+    with_recorder(|recorder| {
+        if let Some(node) = recorder.open.last_mut() {
+            node.surface = surface;
+            node.reflected = reflected;
+            node.refracted = refracted;
+        }
+    });
+}
+
+pub fn ray_tree_to_json(nodes: &[RayNode]) -> String {
+    let nodes: Vec<String> = nodes.iter().map(ray_node_to_json).collect();
+    format!("[{}]", nodes.join(","))
+}
+
+fn ray_node_to_json(node: &RayNode) -> String {
+    let kind = match node.ray.kind {
+        | RayKind::Camera => "camera",
+        | RayKind::Shadow => "shadow",
+        | RayKind::Reflection => "reflection",
+        | RayKind::Refraction => "refraction",
+    };
+    let hit = match node.hit {
+        | None => "null".to_string(),
+        | Some(hit) => format!("{{\"object\":\"{}\",\"t\":{},\"point\":{},\"normal\":{},\"inside\":{},\"n1\":{},\"n2\":{}}}",
+                               hit.object, json_number(hit.t), json_tuple(hit.point), json_tuple(hit.normal_v),
+                               hit.inside, json_number(hit.n1), json_number(hit.n2))
+    };
+    format!("{{\"kind\":\"{}\",\"origin\":{},\"direction\":{},\"time\":{},\"hit\":{},\"color\":{},\"surface\":{},\"reflected\":{},\"refracted\":{},\"children\":{}}}",
+            kind, json_tuple(node.ray.origin), json_tuple(node.ray.direction), json_number(node.ray.time), hit,
+            json_color(node.color), json_color(node.surface), json_color(node.reflected), json_color(node.refracted),
+            ray_tree_to_json(&node.children))
+}
+
+fn json_number(n: f32) -> String {
+    if n.is_finite() { n.to_string() } else { "null".to_string() }
+}
+
+fn json_tuple(t: Tuple) -> String {
+    format!("[{},{},{}]", json_number(t.x), json_number(t.y), json_number(t.z))
+}
+
+fn json_color(c: Color) -> String {
+    format!("[{},{},{}]", json_number(c.red), json_number(c.green), json_number(c.blue))
+}
+
+fn record_ray_end(c: Color) {
+    with_recorder(|recorder| {
+        if let Some(mut node) = recorder.open.pop() {
+            node.color = c;
+            match recorder.open.last_mut() {
+                | Some(parent) => parent.children.push(node),
+                | None => recorder.roots.push(node)
+            }
+        }
+    });
+}
+
+fn render_region(camera: &Camera, settings: &RenderSettings) -> Tile {
+    match settings.crop {
+        | None => Tile { x: 0, y: 0, width: camera.hsize, height: camera.vsize },
+        | Some(crop) => {
+            let x = crop.x.clamp(0, camera.hsize);
+            let y = crop.y.clamp(0, camera.vsize);
+            let width = (crop.x + crop.width).clamp(x, camera.hsize) - x;
+            let height = (crop.y + crop.height).clamp(y, camera.vsize) - y;
+            Tile { x, y, width, height }
         }
     }
-    c
+}
+
+fn output_canvas(camera: &Camera, settings: &RenderSettings) -> (Canvas, (i32, i32)) {
+    let region = render_region(camera, settings);
+    if settings.crop_output {
+        (Canvas::new(region.width, region.height), (region.x, region.y))
+    } else {
+        (Canvas::new(camera.hsize, camera.vsize), (0, 0))
+    }
+}
+
+pub fn tiles(width: i32, height: i32, tile_size: i32) -> Vec<Tile> {
+    let size = tile_size.max(1);
+    let mut tiles = vec![];
+    for y in (0..height).step_by(size as usize) {
+        for x in (0..width).step_by(size as usize) {
+            tiles.push(Tile { x, y, width: size.min(width - x), height: size.min(height - y) });
+        }
+    }
+    tiles
+}
+
+fn render_tile(camera: &Camera, world: &World, tile: Tile, settings: &RenderSettings, samples: Range<u32>,
+               with_aovs: bool) -> Vec<(Color, Option<[Color; 5]>)> {
+    let mut pixels = Vec::with_capacity((tile.width * tile.height) as usize);
+    for y in tile.y..tile.y + tile.height {
+        for x in tile.x..tile.x + tile.width {
+            pixels.push(pixel_color(camera, world, x, y, settings, samples.clone(), with_aovs));
+        }
+    }
+    pixels
+}
+
+fn pixel_color(camera: &Camera, world: &World, x: i32, y: i32, settings: &RenderSettings, samples: Range<u32>,
+               with_aovs: bool) -> (Color, Option<[Color; 5]>) {
+    let scale = 1.0 / samples.len() as f32;
+    let mut sum = color(0.0, 0.0, 0.0);
+    let mut aovs = with_aovs.then_some([color(0.0, 0.0, 0.0); 5]);
+    for sample in samples {
+        let ray = ray_for_pixel_sample(camera, x, y, sample);
+        sum = sum + integrate_within(world, ray, settings.integrator, settings);
+        if let Some(aovs) = aovs.as_mut() {
+            for (total, value) in aovs.iter_mut().zip(aov_sample(camera, world, ray)) {
+                *total = *total + value * scale;
+            }
+        }
+    }
+    (sum * scale, aovs)
+}
+
+pub fn render_settings() -> RenderSettings {
+    RenderSettings {
+        max_reflection_depth: DEFAULT_REFLECTION_NUMBER,
+        max_refraction_depth: DEFAULT_REFLECTION_NUMBER,
+        min_contribution: 0.0,
+        max_path_depth: DEFAULT_PATH_DEPTH,
+        samples: 1,
+        threads: 0,
+        tile_size: 16,
+        integrator: Integrator::Whitted,
+        crop: None,
+        crop_output: false,
+        tone_mapping: tone_mapping(),
+    }
+}
+
+pub fn animation() -> Animation {
+    Animation { camera: None, lights: vec![], objects: vec![], materials: vec![] }
+}
+
+pub fn frame_time(frame: u32, fps: f32) -> Result<f32, String> {
+    if !(fps.is_finite() && fps > 0.0) {
+        return Err(format!("Frame rate must be positive, got {fps}"));
+    }
+    Ok(frame as f32 / fps)
+}
+
+pub fn frame_file_name(prefix: &str, frame: u32) -> String {
+    format!("{prefix}{frame:04}.ppm")
+}
+
+pub fn render_animation<F>(camera: &mut Camera, world: &mut World, animation: &Animation, settings: &RenderSettings,
+                           frames: Range<u32>, fps: f32, mut on_frame: F) -> Result<(), String>
+    where F: FnMut(u32, Canvas) {
+    for frame in frames {
+        animation.apply(frame_time(frame, fps)?, camera, world)?;
+        on_frame(frame, render_with_settings(camera, world, settings));
+    }
+    Ok(())
+}
+
+pub fn write_animation(camera: &mut Camera, world: &mut World, animation: &Animation, settings: &RenderSettings,
+                       frames: Range<u32>, fps: f32, prefix: &str) -> std::io::Result<Vec<String>> {
+    let mut files = vec![];
+    for frame in frames {
+        frame_time(frame, fps)
+            .and_then(|time| animation.apply(time, camera, world))
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+        let name = frame_file_name(prefix, frame);
+        let mut f = File::create(&name)?;
+        f.write_all(canvas_to_ppm(render_with_settings(camera, world, settings)).as_bytes())?;
+        files.push(name);
+    }
+    Ok(files)
 }
 
 pub fn is_shadowed(w: &World, p: Point) -> bool {
+    is_shadowed_at(w, p, 0.0)
+}
+
+pub fn is_shadowed_at(w: &World, p: Point, time: f32) -> bool {
     let v = w.lights[0].position - p;
-    let distance = magnitude(v);
-    let direction = normalize(v);
-    let r = ray(p, direction);
+    is_occluded(w, p, normalize(v), magnitude(v), time)
+}
 
-    let intersections = intersect_world(w, r);
-    let h = hit(&intersections);
+pub fn is_occluded(w: &World, p: Point, direction: Vector, distance: f32, time: f32) -> bool {
+    shadow_attenuation(w, p, direction, distance, time) == color(0.0, 0.0, 0.0)
+}
 
-    match h {
-        | Some(intersection) => intersection.t < distance,
-        | None => false,
+pub fn shadow_attenuation(w: &World, p: Point, direction: Vector, distance: f32, time: f32) -> Color {
+    let r = ray_of_kind(p, direction, time, RayKind::Shadow);
+    record_ray_start(r);
+    let attenuation = shadow_attenuation_along(w, r, distance);
+    record_ray_end(attenuation);
+    attenuation
+}
+
+fn shadow_attenuation_along(w: &World, r: Ray, distance: f32) -> Color {
+    let mut attenuation = color(1.0, 1.0, 1.0);
+    let xs = intersect_world(w, r);
+    let mut crossed: Vec<Uuid> = vec![];
+    for i in xs.iter().filter(|i| !i.object.is_volume() && i.t >= 0.0 && i.t < distance) {
+        let m = i.object.material();
+        if m.transparency <= 0.0 {
+            return color(0.0, 0.0, 0.0);
+        }
+        if !crossed.contains(&i.object.id()) {
+            crossed.push(i.object.id());
+            attenuation = attenuation * surface_color_at(m, i.object, position(r, i.t), r.time) * m.transparency;
+        }
+    }
+    let length = magnitude(r.direction);
+    for (object, enter, exit) in spans(&xs) {
+        let inside = (exit.min(distance) - enter.max(0.0)).max(0.0) * length;
+        attenuation = attenuation * match object.as_any().downcast_ref::<ConstantMedium>() {
+            | Some(medium) => color(1.0, 1.0, 1.0) * (-medium.density * inside).exp(),
+            | None => transmittance(object.material(), inside)
+        };
     }
+    attenuation
 }
 
 fn append_string_or_new_line(c: f32, line_len: usize) -> (String, usize, bool) {
@@ -1745,6 +3822,207 @@ fn append_string_or_new_line(c: f32, line_len: usize) -> (String, usize, bool) {
     (content, new_line_len, new_line)
 }
 
+fn ppm_token<'a>(data: &'a [u8], pos: &mut usize) -> Result<&'a [u8], String> {
+    loop {
+        while *pos < data.len() && data[*pos].is_ascii_whitespace() {
+            *pos += 1;
+        }
+        if *pos < data.len() && data[*pos] == b'#' {
+            while *pos < data.len() && data[*pos] != b'\n' {
+                *pos += 1;
+            }
+        } else {
+            break;
+        }
+    }
+    let start = *pos;
+    while *pos < data.len() && !data[*pos].is_ascii_whitespace() {
+        *pos += 1;
+    }
+    match start == *pos {
+        | true => Err("Unexpected end of PPM data".to_string()),
+        | false => Ok(&data[start..*pos])
+    }
+}
+
+fn ppm_number(data: &[u8], pos: &mut usize) -> Result<i32, String> {
+    let token = ppm_token(data, pos)?;
+    std::str::from_utf8(token).ok()
+        .and_then(|t| t.parse::<i32>().ok())
+        .ok_or_else(|| format!("Invalid number in PPM data: {}", String::from_utf8_lossy(token)))
+}
+
+pub fn ppm_to_canvas(data: &[u8]) -> Result<Canvas, String> {
+    let mut pos = 0;
+    let magic = ppm_token(data, &mut pos)?;
+    let binary = match magic {
+        | b"P3" => false,
+        | b"P6" => true,
+        | _ => return Err(format!("Unsupported PPM format: {}", String::from_utf8_lossy(magic)))
+    };
+    let width = ppm_number(data, &mut pos)?;
+    let height = ppm_number(data, &mut pos)?;
+    let max = ppm_number(data, &mut pos)?;
+    if width <= 0 || height <= 0 || !(1..=65535).contains(&max) {
+        return Err(format!("Invalid PPM header: {width} {height} {max}"));
+    }
+
+    let scale = max as f32;
+    pos += 1;
+    let sample_size = if max > 255 { 2 } else { 1 };
+    let remaining = data.len().saturating_sub(pos);
+    let fits = width.checked_mul(height)
+        .and_then(|pixels| (pixels as usize).checked_mul(3 * if binary { sample_size } else { 2 }))
+        .is_some_and(|needed| needed <= remaining + if binary { 0 } else { 1 });
+    if !fits {
+        return Err(format!("PPM data too short for a {width}x{height} image"));
+    }
+
+    let mut canvas = Canvas::new(width, height);
+    let next_sample = |pos: &mut usize| -> Result<f32, String> {
+        if !binary {
+            return Ok(ppm_number(data, pos)? as f32 / scale);
+        }
+        if *pos + sample_size > data.len() {
+            return Err("Unexpected end of PPM data".to_string());
+        }
+        let value = if sample_size == 2 {
+            ((data[*pos] as u32) << 8) | data[*pos + 1] as u32
+        } else {
+            data[*pos] as u32
+        };
+        *pos += sample_size;
+        Ok(value as f32 / scale)
+    };
+
+    for y in 0..height {
+        for x in 0..width {
+            let red = next_sample(&mut pos)?;
+            let green = next_sample(&mut pos)?;
+            let blue = next_sample(&mut pos)?;
+            canvas.write_pixel(x, y, color(red, green, blue));
+        }
+    }
+    Ok(canvas)
+}
+
+pub fn load_ppm(path: &str) -> Result<Canvas, String> {
+    let data = std::fs::read(path).map_err(|e| format!("{path}: {e}"))?;
+    ppm_to_canvas(&data)
+}
+
+pub fn compare_images(a: &Canvas, b: &Canvas) -> Result<ImageDiff, String> {
+    if a.width != b.width || a.height != b.height {
+        return Err(format!("image sizes differ: {}x{} and {}x{}", a.width, a.height, b.width, b.height));
+    }
+    let mut squared = 0.0;
+    let mut max_diff: f32 = 0.0;
+    let mut heat_map = Canvas::new(a.width, a.height);
+    for (i, (p, q)) in a.pixels.iter().zip(b.pixels.iter()).enumerate() {
+        let d = [(p.red - q.red).abs(), (p.green - q.green).abs(), (p.blue - q.blue).abs()];
+        squared += d.iter().map(|c| c * c).sum::<f32>();
+        let pixel_diff = d[0].max(d[1]).max(d[2]);
+        max_diff = max_diff.max(pixel_diff);
+        heat_map.pixels[i] = heat_color(pixel_diff);
+    }
+    let samples = (a.pixels.len() * 3).max(1) as f32;
+    let rmse = (squared / samples).sqrt();
+    let psnr = if rmse > 0.0 { -20.0 * rmse.log10() } else { f32::INFINITY };
+    Ok(ImageDiff { rmse, psnr, max_diff, heat_map })
+}
+
+fn heat_color(v: f32) -> Color {
+    let t = 3.0 * v.clamp(0.0, 1.0);
+    color(t.clamp(0.0, 1.0), (t - 1.0).clamp(0.0, 1.0), (t - 2.0).clamp(0.0, 1.0))
+}
+
+pub fn denoise_settings() -> DenoiseSettings {
+    DenoiseSettings { radius: 3, spatial_sigma: 2.0, color_sigma: 0.5, normal_sigma: 0.3, albedo_sigma: 0.1 }
+}
+
+/// Joint bilateral filter, neighbours across a normal or albedo edge of the guides barely count
+pub fn denoise(image: &Canvas, guides: Option<&Aovs>, settings: &DenoiseSettings) -> Result<Canvas, String> {
+    if let Some(aovs) = guides {
+        for (name, guide) in [("normal", &aovs.normal), ("albedo", &aovs.albedo)] {
+            if (guide.width, guide.height) != (image.width, image.height) {
+                return Err(format!("The {name} guide is {}x{} but the image is {}x{}",
+                                   guide.width, guide.height, image.width, image.height));
+            }
+        }
+    }
+    let radius = settings.radius.max(0);
+    let gaussian = |d2: f32, sigma: f32| if sigma > 0.0 { (-d2 / (2.0 * sigma * sigma)).exp() } else { 1.0 };
+    let distance2 = |a: Color, b: Color| {
+        let d = a - b;
+        d.red * d.red + d.green * d.green + d.blue * d.blue
+    };
+    let mut result = Canvas::new(image.width, image.height);
+    for y in 0..image.height {
+        for x in 0..image.width {
+            let center = image.pixel_at(x, y);
+            let mut sum = color(0.0, 0.0, 0.0);
+            let mut total = 0.0;
+            for ny in (y - radius).max(0)..=(y + radius).min(image.height - 1) {
+                for nx in (x - radius).max(0)..=(x + radius).min(image.width - 1) {
+                    let neighbour = image.pixel_at(nx, ny);
+                    let spatial = ((nx - x) * (nx - x) + (ny - y) * (ny - y)) as f32;
+                    let mut weight = gaussian(spatial, settings.spatial_sigma)
+                        * gaussian(distance2(center, neighbour), settings.color_sigma);
+                    if let Some(aovs) = guides {
+                        weight *= gaussian(distance2(aovs.normal.pixel_at(x, y), aovs.normal.pixel_at(nx, ny)), settings.normal_sigma)
+                            * gaussian(distance2(aovs.albedo.pixel_at(x, y), aovs.albedo.pixel_at(nx, ny)), settings.albedo_sigma);
+                    }
+                    sum = sum + neighbour * weight;
+                    total += weight;
+                }
+            }
+            result.write_pixel(x, y, sum * (1.0 / total));
+        }
+    }
+    Ok(result)
+}
+
+pub fn tone_mapping() -> ToneMapping {
+    ToneMapping { exposure: 0.0, operator: ToneMap::Linear, srgb: false }
+}
+
+pub fn tone_map(c: &Canvas, t: &ToneMapping) -> Canvas {
+    c.map(|p| tone_map_color(p, t))
+}
+
+fn tone_map_color(c: Color, t: &ToneMapping) -> Color {
+    let scale = 2f32.powf(t.exposure);
+    let map = |v: f32| {
+        let v = match t.operator {
+            | ToneMap::Linear => v * scale,
+            | ToneMap::Reinhard => reinhard(v * scale),
+            | ToneMap::Aces => aces(v * scale),
+        };
+        if t.srgb { srgb_encode(v) } else { v }
+    };
+    color(map(c.red), map(c.green), map(c.blue))
+}
+
+pub fn reinhard(v: f32) -> f32 {
+    let v = v.max(0.0);
+    v / (1.0 + v)
+}
+
+/// Narkowicz's fit of the ACES filmic curve
+pub fn aces(v: f32) -> f32 {
+    let v = v.max(0.0);
+    ((v * (2.51 * v + 0.03)) / (v * (2.43 * v + 0.59) + 0.14)).clamp(0.0, 1.0)
+}
+
+pub fn srgb_encode(v: f32) -> f32 {
+    let v = v.clamp(0.0, 1.0);
+    if v <= 0.0031308 {
+        v * 12.92
+    } else {
+        1.055 * v.powf(1.0 / 2.4) - 0.055
+    }
+}
+
 pub fn canvas_to_ppm(c: Canvas) -> String {
     let mut content = String::from("P3\n");
     content.push_str(format!("{} {}\n", c.width, c.height).as_str());
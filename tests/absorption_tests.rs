@@ -0,0 +1,64 @@
+use ray_tracer_challenge::*;
+
+#[cfg(test)]
+mod absorption {
+    use super::*;
+
+    fn tinted_slab(thickness: f32) -> World {
+        let mut w = world();
+        w.background = Background::Solid(color(1.0, 1.0, 1.0));
+        let mut slab = cube();
+        slab.set_transform(scaling(2.0, 2.0, thickness / 2.0));
+        let m = slab.mut_material();
+        m.color = color(0.0, 0.0, 0.0);
+        m.ambient = 0.0;
+        m.transparency = 1.0;
+        m.refractive_index = 1.0;
+        m.absorption = color(0.5, 1.0, 0.8);
+        m.absorption_density = 1.0;
+        w.objects.push(slab);
+        w
+    }
+
+    #[test]
+    /// Materials do not absorb light by default
+    fn materials_do_not_absorb_by_default() {
+        let m = material();
+
+        assert_eq!(m.absorption, color(1.0, 1.0, 1.0));
+        assert_eq!(m.absorption_density, 0.0);
+        assert_eq!(transmittance(&m, 100.0), color(1.0, 1.0, 1.0));
+    }
+
+    #[test]
+    /// Transmittance falls off exponentially with distance and density
+    fn transmittance_falls_off_exponentially() {
+        let mut m = material();
+        m.absorption = color(0.5, 1.0, 0.25);
+        m.absorption_density = 2.0;
+
+        assert_eq!(transmittance(&m, 0.0), color(1.0, 1.0, 1.0));
+        assert_eq!(transmittance(&m, 0.5), color(0.5, 1.0, 0.25));
+        assert_eq!(transmittance(&m, 1.0), color(0.25, 1.0, 0.0625));
+    }
+
+    #[test]
+    /// Thick glass is tinted more than thin glass
+    fn thick_glass_is_tinted_more_than_thin_glass() {
+        let r = ray(point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0));
+
+        let thin = color_at(&tinted_slab(1.0), r, DEFAULT_REFLECTION_NUMBER);
+        let thick = color_at(&tinted_slab(2.0), r, DEFAULT_REFLECTION_NUMBER);
+
+        assert_eq!(thin, color(0.5, 1.0, 0.8));
+        assert_eq!(thick, color(0.25, 1.0, 0.64));
+    }
+
+    #[test]
+    /// The path tracer absorbs light inside glass as well
+    fn path_tracer_absorbs_light_inside_glass() {
+        let r = ray(point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0));
+
+        assert_eq!(path_trace(&tinted_slab(2.0), r, DEFAULT_PATH_DEPTH), color(0.25, 1.0, 0.64));
+    }
+}
@@ -0,0 +1,114 @@
+use std::f32::consts::PI;
+use ray_tracer_challenge::*;
+
+#[cfg(test)]
+mod animation {
+    use super::*;
+
+    #[test]
+    /// Frame numbers are converted to time with the frame rate
+    fn frame_numbers_are_converted_to_time() {
+        assert_eq!(frame_time(0, 24.0), Ok(0.0));
+        assert_eq!(frame_time(12, 24.0), Ok(0.5));
+        assert_eq!(frame_time(48, 24.0), Ok(2.0));
+    }
+
+    #[test]
+    /// A frame rate that is not positive is rejected
+    fn non_positive_frame_rates_are_rejected() {
+        assert!(frame_time(1, 0.0).is_err());
+        assert!(frame_time(1, -24.0).is_err());
+        assert!(frame_time(1, f32::NAN).is_err());
+    }
+
+    #[test]
+    /// Frames are written to numbered files
+    fn frames_are_written_to_numbered_files() {
+        assert_eq!(frame_file_name("turntable", 7), "turntable0007.ppm");
+        assert_eq!(frame_file_name("out/frame_", 123), "out/frame_0123.ppm");
+    }
+
+    #[test]
+    /// Applying an animation moves the camera, lights and objects
+    fn applying_an_animation() {
+        let mut w = default_world();
+        let mut c = camera(11, 11, PI/2.0);
+
+        let mut track = keyframe_track();
+        track.add(keyframe(0.0, vector(0.0, 0.0, 0.0), Quaternion::identity(), vector(1.0, 1.0, 1.0)));
+        track.add(keyframe(1.0, vector(0.0, 2.0, 0.0), Quaternion::identity(), vector(1.0, 1.0, 1.0)));
+
+        let mut a = animation();
+        a.animate_camera(|t| translation(0.0, 0.0, -t));
+        a.animate_light(0, |t| point_light(point(t, 10.0, -10.0), color(1.0, 1.0, 1.0)));
+        a.animate_object(1, move |t| track.transform_at(t));
+        a.animate_material(0, |t, m| m.ambient = t);
+
+        a.apply(0.5, &mut c, &mut w).unwrap();
+
+        assert_eq!(c.transform, translation(0.0, 0.0, -0.5));
+        assert_eq!(w.lights[0].position, point(0.5, 10.0, -10.0));
+        assert_eq!(w.objects[1].transform(), translation(0.0, 1.0, 0.0));
+        assert_eq!(w.objects[0].material().ambient, 0.5);
+    }
+
+    #[test]
+    /// Rendering an animation produces one canvas per frame
+    fn rendering_an_animation_produces_a_canvas_per_frame() {
+        let mut w = default_world();
+        let mut c = camera(5, 5, PI/2.0);
+
+        let mut a = animation();
+        a.animate_camera(|t| view_transformation(
+            point(0.0, 0.0, -5.0 - t),
+            point(0.0, 0.0, 0.0),
+            vector(0.0, 1.0, 0.0)));
+
+        let mut frames = vec![];
+        render_animation(&mut c, &mut w, &a, &render_settings(), 3..6, 2.0, |frame, canvas| frames.push((frame, canvas))).unwrap();
+
+        assert_eq!(frames.iter().map(|f| f.0).collect::<Vec<_>>(), vec![3, 4, 5]);
+        assert!(frames.iter().all(|f| f.1.width == 5 && f.1.height == 5));
+        assert_eq!(c.transform, view_transformation(
+            point(0.0, 0.0, -7.5),
+            point(0.0, 0.0, 0.0),
+            vector(0.0, 1.0, 0.0)));
+    }
+
+    #[test]
+    /// Animating a light or object that does not exist is an error
+    fn animating_a_missing_index_is_an_error() {
+        let mut w = default_world();
+        let mut c = camera(5, 5, PI/2.0);
+
+        let mut a = animation();
+        a.animate_camera(|t| translation(0.0, 0.0, -t));
+        a.animate_object(2, |t| translation(t, 0.0, 0.0));
+
+        assert!(a.apply(0.5, &mut c, &mut w).is_err());
+        assert_eq!(c.transform, Matrix::identity4x4());
+
+        let mut a = animation();
+        a.animate_light(1, |t| point_light(point(t, 10.0, -10.0), color(1.0, 1.0, 1.0)));
+
+        assert!(a.apply(0.5, &mut c, &mut w).is_err());
+        assert!(render_animation(&mut c, &mut w, &a, &render_settings(), 0..2, 24.0, |_, _| {}).is_err());
+    }
+
+    #[test]
+    /// Animation frames are rendered with the given settings
+    fn animation_frames_use_the_render_settings() {
+        let mut w = default_world();
+        let mut c = camera(5, 5, PI/2.0);
+        let mut a = animation();
+        a.animate_camera(|_| view_transformation(point(0.0, 0.0, -5.0), point(0.0, 0.0, 0.0), vector(0.0, 1.0, 0.0)));
+        let mut s = render_settings();
+        s.tone_mapping.exposure = 1.0;
+
+        let mut frames = vec![];
+        render_animation(&mut c, &mut w, &a, &s, 0..1, 24.0, |_, canvas| frames.push(canvas)).unwrap();
+
+        assert_eq!(frames[0].pixels, render_with_settings(&c, &w, &s).pixels);
+        assert_ne!(frames[0].pixels, render(&c, &w).pixels);
+    }
+}
@@ -0,0 +1,125 @@
+use std::f32::consts::PI;
+use ray_tracer_challenge::*;
+
+mod common;
+use common::*;
+
+#[cfg(test)]
+mod aovs {
+    use super::*;
+
+    #[test]
+    /// AOVs describe the first surface seen through each pixel
+    fn aovs_describe_the_first_surface() {
+        let w = default_world();
+
+        let aovs = render_with_aovs(&default_camera(), &w, &render_settings()).1;
+
+        assert_eq!(aovs.depth.pixel_at(5, 5), color(4.0, 4.0, 4.0));
+        assert_eq!(aovs.normal.pixel_at(5, 5), color(0.0, 0.0, -1.0));
+        assert_eq!(aovs.albedo.pixel_at(5, 5), color(0.8, 1.0, 0.6));
+        assert_eq!(aovs.object_id.pixel_at(5, 5), object_id_color(w.objects[0].id()));
+        assert_eq!(aovs.shadow.pixel_at(5, 5), color(1.0, 1.0, 1.0));
+    }
+
+    #[test]
+    /// Pixels that miss everything are black in every AOV
+    fn missed_pixels_are_black() {
+        let w = default_world();
+
+        let aovs = render_with_aovs(&default_camera(), &w, &render_settings()).1;
+
+        for canvas in [&aovs.depth, &aovs.normal, &aovs.albedo, &aovs.object_id, &aovs.shadow] {
+            assert_eq!(canvas.pixel_at(0, 0), color(0.0, 0.0, 0.0));
+        }
+    }
+
+    #[test]
+    /// Different objects get different id colors
+    fn different_objects_get_different_id_colors() {
+        let a = sphere();
+        let b = sphere();
+
+        assert_eq!(object_id_color(a.id()), object_id_color(a.id()));
+        assert_ne!(object_id_color(a.id()), object_id_color(b.id()));
+    }
+
+    #[test]
+    /// The shadow mask is black where the lights are blocked
+    fn shadow_mask_is_black_in_shadow() {
+        let mut w = world();
+        w.lights.push(point_light(point(0.0, 10.0, 0.0), color(1.0, 1.0, 1.0)));
+        w.objects.push(plane());
+        let mut blocker = sphere();
+        blocker.set_transform(translation(0.0, 2.0, 0.0));
+        blocker.set_visibility(Visibility { camera: false, ..visibility() });
+        w.objects.push(blocker);
+        let mut c = camera(11, 11, PI / 2.0);
+        c.transform = view_transformation(point(0.0, 5.0, -5.0), point(0.0, 0.0, 0.0), vector(0.0, 1.0, 0.0));
+
+        let aovs = render_with_aovs(&c, &w, &render_settings()).1;
+
+        assert_eq!(aovs.shadow.pixel_at(5, 5), color(0.0, 0.0, 0.0));
+        assert_eq!(aovs.shadow.pixel_at(5, 10), color(1.0, 1.0, 1.0));
+    }
+
+    #[test]
+    /// AOVs follow the crop window of the render
+    fn aovs_follow_the_crop_window() {
+        let w = default_world();
+        let mut s = render_settings();
+        s.crop = Some(Tile { x: 4, y: 4, width: 3, height: 3 });
+        s.crop_output = true;
+
+        let (image, aovs) = render_with_aovs(&default_camera(), &w, &s);
+
+        assert_eq!((aovs.depth.width, aovs.depth.height), (3, 3));
+        assert_eq!(aovs.depth.pixel_at(1, 1), color(4.0, 4.0, 4.0));
+        assert_eq!(image.pixel_at(1, 1), color(0.38066, 0.47583, 0.2855));
+    }
+
+    #[test]
+    /// Depth is measured along the view axis rather than along the ray
+    fn depth_is_measured_along_the_view_axis() {
+        let mut w = world();
+        let mut wall = plane();
+        wall.set_transform(rotation_x(PI / 2.0));
+        w.objects.push(wall);
+
+        let aovs = render_with_aovs(&default_camera(), &w, &render_settings()).1;
+
+        assert_eq!(aovs.depth.pixel_at(5, 5), color(5.0, 5.0, 5.0));
+        assert_eq!(aovs.depth.pixel_at(0, 0), color(5.0, 5.0, 5.0));
+    }
+
+    #[test]
+    /// AOVs average the same samples as the image, across the shutter
+    fn aovs_average_the_samples_of_the_image() {
+        let mut w = world();
+        let mut s = sphere();
+        s.set_transform(translation(-2.0, 0.0, 0.0) * scaling(0.5, 0.5, 0.5));
+        s.set_end_transform(Some(translation(2.0, 0.0, 0.0) * scaling(0.5, 0.5, 0.5)));
+        w.objects.push(s);
+        let mut c = camera(11, 1, PI / 2.0);
+        c.transform = view_transformation(point(0.0, 0.0, -5.0), point(0.0, 0.0, 0.0), vector(0.0, 1.0, 0.0));
+        c.shutter_close = 1.0;
+        let mut settings = render_settings();
+        settings.samples = 32;
+
+        let albedo = render_with_aovs(&c, &w, &settings).1.albedo.pixel_at(5, 0);
+
+        assert!(albedo.red > 0.05 && albedo.red < 0.95);
+    }
+
+    #[test]
+    /// The image that comes with the AOVs is left in linear color
+    fn image_with_aovs_is_linear() {
+        let w = default_world();
+        let mut s = render_settings();
+        s.tone_mapping = ToneMapping { operator: ToneMap::Aces, srgb: true, ..tone_mapping() };
+
+        let (image, _) = render_with_aovs(&default_camera(), &w, &s);
+
+        assert_eq!(image.pixels, render_with_settings(&default_camera(), &w, &render_settings()).pixels);
+    }
+}
@@ -0,0 +1,155 @@
+use std::f32::consts::PI;
+use std::ops::DerefMut;
+use ray_tracer_challenge::*;
+
+#[cfg(test)]
+mod background {
+    use super::*;
+
+    fn four_color_map() -> EnvironmentMap {
+        // columns wrap around the horizon, starting behind the viewer (+z)
+        let mut image = Canvas::new(4, 1);
+        image.write_pixel(0, 0, color(1.0, 0.0, 0.0));
+        image.write_pixel(1, 0, color(0.0, 1.0, 0.0));
+        image.write_pixel(2, 0, color(0.0, 0.0, 1.0));
+        image.write_pixel(3, 0, color(1.0, 1.0, 0.0));
+        environment_map(image).unwrap()
+    }
+
+    #[test]
+    /// A world has a black background by default
+    fn world_has_a_black_background_by_default() {
+        let w = world();
+        let r = ray(point(0.0, 0.0, -5.0), vector(0.0, 1.0, 0.0));
+
+        assert_eq!(color_at(&w, r, DEFAULT_REFLECTION_NUMBER), color(0.0, 0.0, 0.0));
+    }
+
+    #[test]
+    /// A ray that misses returns the solid background color
+    fn ray_that_misses_returns_the_solid_background() {
+        let mut w = default_world();
+        w.background = Background::Solid(color(0.2, 0.3, 0.4));
+        let r = ray(point(0.0, 0.0, -5.0), vector(0.0, 1.0, 0.0));
+
+        assert_eq!(color_at(&w, r, DEFAULT_REFLECTION_NUMBER), color(0.2, 0.3, 0.4));
+    }
+
+    #[test]
+    /// A gradient sky blends from bottom to top with the ray direction
+    fn gradient_sky_blends_with_the_direction() {
+        let b = Background::Gradient { top: color(0.0, 0.0, 1.0), bottom: color(1.0, 1.0, 1.0) };
+
+        assert_eq!(background_color(&b, vector(0.0, 1.0, 0.0)), color(0.0, 0.0, 1.0));
+        assert_eq!(background_color(&b, vector(0.0, -1.0, 0.0)), color(1.0, 1.0, 1.0));
+        assert_eq!(background_color(&b, vector(0.0, 0.0, 5.0)), color(0.5, 0.5, 1.0));
+    }
+
+    #[test]
+    /// An environment map is looked up by the ray direction
+    fn environment_map_is_looked_up_by_direction() {
+        let b = Background::Environment(four_color_map());
+
+        assert_eq!(background_color(&b, vector(-1.0, 0.0, 1.0)), color(1.0, 0.0, 0.0));
+        assert_eq!(background_color(&b, vector(-1.0, 0.0, -1.0)), color(0.0, 1.0, 0.0));
+        assert_eq!(background_color(&b, vector(1.0, 0.0, -1.0)), color(0.0, 0.0, 1.0));
+        assert_eq!(background_color(&b, vector(1.0, 0.0, 1.0)), color(1.0, 1.0, 0.0));
+    }
+
+    #[test]
+    /// An environment map is filtered between texels and scaled by its intensity
+    fn environment_map_is_filtered_and_scaled() {
+        let mut map = four_color_map();
+        map.intensity = 2.0;
+        let b = Background::Environment(map);
+        let direction = vector((PI/8.0).sin(), 0.0, -(PI/8.0).cos());
+
+        assert_eq!(background_color(&b, vector(0.0, 0.0, -1.0)), color(0.0, 1.0, 1.0));
+        assert_eq!(background_color(&b, direction), color(0.0, 0.5, 1.5));
+    }
+
+    #[test]
+    /// An environment map keeps its image and rejects an empty one
+    fn environment_map_keeps_its_image_and_rejects_an_empty_one() {
+        let map = four_color_map();
+        assert_eq!(map.image().width, 4);
+        assert_eq!(map.image().pixel_at(2, 0), color(0.0, 0.0, 1.0));
+
+        assert!(environment_map(Canvas::new(0, 4)).is_err());
+        assert!(environment_map(Canvas::new(4, 0)).is_err());
+    }
+
+    #[test]
+    /// A reflective surface reflects the background
+    fn reflective_surface_reflects_the_background() {
+        let mut w = world();
+        w.lights.push(point_light(point(-10.0, 10.0, -10.0), color(1.0, 1.0, 1.0)));
+        w.background = Background::Gradient { top: color(0.0, 0.0, 1.0), bottom: color(0.0, 0.0, 0.0) };
+        let mut floor = plane();
+        let floor_ref = floor.deref_mut();
+        floor_ref.mut_material().color = color(0.0, 0.0, 0.0);
+        floor_ref.mut_material().specular = 0.0;
+        floor_ref.mut_material().reflective = 1.0;
+        w.objects.push(floor);
+
+        let r = ray(point(0.0, 1.0, 0.0), vector(0.0, -1.0, 0.0));
+
+        assert_eq!(color_at(&w, r, DEFAULT_REFLECTION_NUMBER), color(0.0, 0.0, 1.0));
+    }
+
+    #[test]
+    /// Reading a plain PPM file into a canvas
+    fn reading_a_plain_ppm() {
+        let ppm = "P3\n# a comment\n2 1\n255\n255 0 0\n0 127.5 255\n";
+        assert!(ppm_to_canvas(ppm.as_bytes()).is_err());
+
+        let ppm = "P3\n# a comment\n2 1\n255\n255 0 0 0 51 255\n";
+        let c = ppm_to_canvas(ppm.as_bytes()).unwrap();
+
+        assert_eq!(c.width, 2);
+        assert_eq!(c.height, 1);
+        assert_eq!(c.pixel_at(0, 0), color(1.0, 0.0, 0.0));
+        assert_eq!(c.pixel_at(1, 0), color(0.0, 0.2, 1.0));
+    }
+
+    #[test]
+    /// Reading a binary PPM file into a canvas
+    fn reading_a_binary_ppm() {
+        let mut ppm = b"P6 1 2 255\n".to_vec();
+        ppm.extend_from_slice(&[255, 0, 51, 0, 255, 0]);
+
+        let c = ppm_to_canvas(&ppm).unwrap();
+
+        assert_eq!(c.pixel_at(0, 0), color(1.0, 0.0, 0.2));
+        assert_eq!(c.pixel_at(0, 1), color(0.0, 1.0, 0.0));
+    }
+
+    #[test]
+    /// A canvas survives a round trip through PPM
+    fn canvas_survives_a_round_trip_through_ppm() {
+        let mut c = Canvas::new(3, 2);
+        c.write_pixel(1, 1, color(0.2, 0.4, 1.0));
+
+        let read = ppm_to_canvas(canvas_to_ppm(c).as_bytes()).unwrap();
+
+        assert_eq!(read.pixel_at(1, 1), color(0.2, 0.4, 1.0));
+        assert_eq!(read.pixel_at(0, 0), color(0.0, 0.0, 0.0));
+    }
+
+    #[test]
+    /// Reading malformed PPM data fails
+    fn reading_malformed_ppm_fails() {
+        assert!(ppm_to_canvas(b"P5 1 1 255 0").is_err());
+        assert!(ppm_to_canvas(b"P3 2 2 255 0 0 0").is_err());
+        assert!(ppm_to_canvas(b"P6 1 1 255\n\x01").is_err());
+        assert!(load_ppm("does-not-exist.ppm").is_err());
+    }
+
+    #[test]
+    /// A header larger than its data is rejected before allocating the canvas
+    fn reading_a_ppm_header_larger_than_its_data_fails() {
+        assert!(ppm_to_canvas(b"P6 60000 60000 255\n\x01\x02\x03").is_err());
+        assert!(ppm_to_canvas(b"P3 60000 60000 255\n0 0 0").is_err());
+        assert!(ppm_to_canvas(b"P6 2000000000 2000000000 255\n\x01").is_err());
+    }
+}
@@ -0,0 +1,145 @@
+use ray_tracer_challenge::*;
+
+#[cfg(test)]
+mod canvas_operations {
+    use super::*;
+
+    // every pixel holds its own coordinates
+    fn coordinates(width: i32, height: i32) -> Canvas {
+        let mut c = Canvas::new(width, height);
+        for y in 0..height {
+            for x in 0..width {
+                c.write_pixel(x, y, color(x as f32, y as f32, 0.0));
+            }
+        }
+        c
+    }
+
+    fn filled(width: i32, height: i32, c: Color) -> Canvas {
+        let mut canvas = Canvas::new(width, height);
+        canvas.pixels = vec![c; (width * height) as usize];
+        canvas
+    }
+
+    #[test]
+    /// Pixel access outside the canvas fails instead of panicking
+    fn pixel_access_outside_the_canvas_fails() {
+        let mut c = Canvas::new(4, 3);
+
+        assert_eq!(c.try_pixel_at(3, 2), Some(color(0.0, 0.0, 0.0)));
+        assert_eq!(c.try_pixel_at(4, 0), None);
+        assert_eq!(c.try_pixel_at(0, -1), None);
+        assert!(c.try_write_pixel(1, 1, color(1.0, 0.0, 0.0)).is_ok());
+        assert!(c.try_write_pixel(0, 3, color(1.0, 0.0, 0.0)).is_err());
+        assert_eq!(c.pixel_at(1, 1), color(1.0, 0.0, 0.0));
+    }
+
+    #[test]
+    /// Cropping keeps the pixels inside the rectangle and clips it to the canvas
+    fn cropping_a_canvas() {
+        let c = coordinates(5, 4);
+
+        let cropped = c.crop(1, 2, 3, 2).unwrap();
+        let clipped = c.crop(3, -1, 10, 3).unwrap();
+
+        assert_eq!((cropped.width, cropped.height), (3, 2));
+        assert_eq!(cropped.pixel_at(0, 0), color(1.0, 2.0, 0.0));
+        assert_eq!(cropped.pixel_at(2, 1), color(3.0, 3.0, 0.0));
+        assert_eq!((clipped.width, clipped.height), (2, 2));
+        assert_eq!(clipped.pixel_at(0, 0), color(3.0, 0.0, 0.0));
+    }
+
+    #[test]
+    /// Cropping to a non-positive size fails
+    fn cropping_to_a_non_positive_size_fails() {
+        let c = coordinates(5, 4);
+
+        assert!(c.crop(1, 1, 0, 2).is_err());
+        assert!(c.crop(1, 1, 2, -1).is_err());
+    }
+
+    #[test]
+    /// Flipping mirrors the canvas along either axis
+    fn flipping_a_canvas() {
+        let c = coordinates(3, 2);
+
+        assert_eq!(c.flip_horizontal().pixel_at(0, 1), color(2.0, 1.0, 0.0));
+        assert_eq!(c.flip_vertical().pixel_at(0, 1), color(0.0, 0.0, 0.0));
+    }
+
+    #[test]
+    /// Mapping applies a function to every pixel
+    fn mapping_every_pixel() {
+        let c = coordinates(3, 2);
+
+        let doubled = c.map(|p| p * 2.0);
+
+        assert_eq!(doubled.pixel_at(2, 1), color(4.0, 2.0, 0.0));
+    }
+
+    #[test]
+    /// Resizing with each filter
+    fn resizing_with_each_filter() {
+        let c = coordinates(4, 4);
+
+        let nearest = c.resize(2, 2, ResizeFilter::Nearest).unwrap();
+        let bilinear = c.resize(8, 8, ResizeFilter::Bilinear).unwrap();
+        let boxed = c.resize(2, 2, ResizeFilter::Box).unwrap();
+
+        assert_eq!(nearest.pixel_at(1, 1), color(3.0, 3.0, 0.0));
+        assert_eq!(bilinear.pixel_at(3, 0), color(1.25, 0.0, 0.0));
+        assert_eq!(bilinear.pixel_at(0, 7), color(0.0, 3.0, 0.0));
+        assert_eq!(boxed.pixel_at(0, 0), color(0.5, 0.5, 0.0));
+        assert_eq!(boxed.pixel_at(1, 1), color(2.5, 2.5, 0.0));
+    }
+
+    #[test]
+    /// Resizing to a non-positive size fails
+    fn resizing_to_a_non_positive_size_fails() {
+        let c = coordinates(4, 4);
+
+        assert!(c.resize(0, 2, ResizeFilter::Nearest).is_err());
+        assert!(c.resize(2, -3, ResizeFilter::Box).is_err());
+    }
+
+    #[test]
+    /// Blending a canvas on top of another with each mode
+    fn blending_canvases() {
+        let base = filled(2, 2, color(0.5, 0.5, 0.5));
+        let top = filled(1, 1, color(0.5, 1.0, 0.0));
+
+        assert_eq!(base.blend(&top, 1, 1, BlendMode::Normal, 1.0).pixel_at(1, 1), color(0.5, 1.0, 0.0));
+        assert_eq!(base.blend(&top, 1, 1, BlendMode::Normal, 0.5).pixel_at(1, 1), color(0.5, 0.75, 0.25));
+        assert_eq!(base.blend(&top, 1, 1, BlendMode::Add, 1.0).pixel_at(1, 1), color(1.0, 1.5, 0.5));
+        assert_eq!(base.blend(&top, 1, 1, BlendMode::Multiply, 1.0).pixel_at(1, 1), color(0.25, 0.5, 0.0));
+        assert_eq!(base.blend(&top, 1, 1, BlendMode::Screen, 1.0).pixel_at(1, 1), color(0.75, 1.0, 0.5));
+        assert_eq!(base.blend(&top, 1, 1, BlendMode::Normal, 1.0).pixel_at(0, 0), color(0.5, 0.5, 0.5));
+        assert_eq!(base.blend(&top, 5, 5, BlendMode::Normal, 1.0).pixels, base.pixels);
+    }
+
+    #[test]
+    /// Convolution kernels
+    fn convolution_kernels() {
+        let mut c = filled(5, 5, color(0.0, 0.0, 0.0));
+        c.write_pixel(2, 2, color(9.0, 9.0, 9.0));
+        let flat = filled(3, 3, color(0.4, 0.4, 0.4));
+
+        assert_eq!(c.convolve(&box_blur(1).unwrap()).pixel_at(1, 1), color(1.0, 1.0, 1.0));
+        assert_eq!(c.convolve(&box_blur(1).unwrap()).pixel_at(0, 0), color(0.0, 0.0, 0.0));
+        assert!((gaussian_blur(2, 1.0).unwrap().weights.iter().sum::<f32>() - 1.0).abs() < EPS);
+        assert_eq!(flat.convolve(&gaussian_blur(1, 1.0).unwrap()).pixels, flat.pixels);
+        assert_eq!(flat.convolve(&sharpen()).pixels, flat.pixels);
+        assert_eq!(c.convolve(&kernel(1, 1, vec![2.0]).unwrap()).pixel_at(2, 2), color(18.0, 18.0, 18.0));
+    }
+
+    #[test]
+    /// Kernels with the wrong number of weights or a degenerate blur are rejected
+    fn invalid_kernels_are_rejected() {
+        assert!(kernel(3, 3, vec![1.0; 8]).is_err());
+        assert!(kernel(0, 3, vec![]).is_err());
+        assert!(box_blur(-1).is_err());
+        assert!(gaussian_blur(1, 0.0).is_err());
+        assert!(gaussian_blur(1, f32::NAN).is_err());
+        assert!(gaussian_blur(-1, 1.0).is_err());
+    }
+}
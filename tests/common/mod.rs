@@ -0,0 +1,20 @@
+#![allow(dead_code)]
+
+use std::f32::consts::PI;
+use ray_tracer_challenge::*;
+
+pub fn default_camera() -> Camera {
+    let mut c = camera(11, 11, PI / 2.0);
+    c.transform = view_transformation(point(0.0, 0.0, -5.0), point(0.0, 0.0, 0.0), vector(0.0, 1.0, 0.0));
+    c
+}
+
+pub fn furnace_world() -> World {
+    let mut w = world();
+    w.background = Background::Solid(color(1.0, 1.0, 1.0));
+    let mut s = sphere();
+    s.mut_material().color = color(0.5, 0.25, 1.0);
+    s.mut_material().diffuse = 1.0;
+    w.objects.push(s);
+    w
+}
@@ -0,0 +1,127 @@
+use ray_tracer_challenge::*;
+
+mod common;
+use common::*;
+
+#[cfg(test)]
+mod crop {
+    use super::*;
+
+    #[test]
+    /// Renders cover the whole frame by default
+    fn renders_cover_the_whole_frame_by_default() {
+        let s = render_settings();
+
+        assert_eq!(s.crop, None);
+        assert!(!s.crop_output);
+    }
+
+    #[test]
+    /// A crop window only traces pixels inside it
+    fn crop_window_only_traces_pixels_inside_it() {
+        let w = default_world();
+        let mut s = render_settings();
+        s.crop = Some(Tile { x: 4, y: 4, width: 3, height: 3 });
+
+        let image = render_with_settings(&default_camera(), &w, &s);
+
+        assert_eq!((image.width, image.height), (11, 11));
+        assert_eq!(image.pixel_at(5, 5), color(0.38066, 0.47583, 0.2855));
+        assert_eq!(image.pixel_at(3, 5), color(0.0, 0.0, 0.0));
+        assert_eq!(image.pixel_at(5, 7), color(0.0, 0.0, 0.0));
+    }
+
+    #[test]
+    /// A cropped output holds just the pixels of the crop window
+    fn cropped_output_holds_just_the_window() {
+        let w = default_world();
+        let mut s = render_settings();
+        s.crop = Some(Tile { x: 4, y: 4, width: 3, height: 2 });
+        s.crop_output = true;
+
+        let full = render(&default_camera(), &w);
+        let image = render_with_settings(&default_camera(), &w, &s);
+
+        assert_eq!((image.width, image.height), (3, 2));
+        assert_eq!(image.pixel_at(1, 1), color(0.38066, 0.47583, 0.2855));
+        assert_eq!(image.pixel_at(2, 0), full.pixel_at(6, 4));
+    }
+
+    #[test]
+    /// A crop window reaching past the frame is clipped to it
+    fn crop_window_is_clipped_to_the_frame() {
+        let w = default_world();
+        let mut s = render_settings();
+        s.crop = Some(Tile { x: 8, y: -2, width: 10, height: 4 });
+        s.crop_output = true;
+
+        let image = render_with_settings(&default_camera(), &w, &s);
+
+        assert_eq!((image.width, image.height), (3, 2));
+    }
+}
+
+#[cfg(test)]
+mod trace_pixel {
+    use super::*;
+
+    #[test]
+    /// Tracing a pixel records the camera ray, its hit and its shadow ray
+    fn tracing_a_pixel_records_camera_ray_hit_and_shadow_ray() {
+        let w = default_world();
+
+        let trees = trace_pixel(&default_camera(), &w, &render_settings(), 5, 5);
+
+        assert_eq!(trees.len(), 1);
+        let root = &trees[0];
+        assert_eq!(root.ray.kind, RayKind::Camera);
+        assert_eq!(root.color, color(0.38066, 0.47583, 0.2855));
+        let hit = root.hit.unwrap();
+        assert_eq!(hit.object, w.objects[0].id());
+        assert!((hit.t - 4.0).abs() < EPS);
+        assert_eq!(hit.point, point(0.0, 0.0, -1.0));
+        assert_eq!(root.children.len(), 1);
+        assert_eq!(root.children[0].ray.kind, RayKind::Shadow);
+        assert_eq!(root.children[0].color, color(1.0, 1.0, 1.0));
+    }
+
+    #[test]
+    /// A pixel that misses everything is a single leaf without a hit
+    fn missed_pixel_is_a_single_leaf() {
+        let w = default_world();
+
+        let trees = trace_pixel(&default_camera(), &w, &render_settings(), 0, 0);
+
+        assert!(trees[0].hit.is_none());
+        assert!(trees[0].children.is_empty());
+    }
+
+    #[test]
+    /// Reflected and refracted rays are children of the ray that spawned them
+    fn secondary_rays_are_children_of_their_parent() {
+        let mut w = default_world();
+        w.objects[0].mut_material().transparency = 1.0;
+        w.objects[0].mut_material().refractive_index = 1.5;
+
+        let trees = trace_pixel(&default_camera(), &w, &render_settings(), 5, 5);
+
+        let kinds: Vec<RayKind> = trees[0].children.iter().map(|n| n.ray.kind).collect();
+        assert!(kinds.contains(&RayKind::Shadow));
+        assert!(kinds.contains(&RayKind::Reflection));
+        assert!(kinds.contains(&RayKind::Refraction));
+        let refracted = trees[0].children.iter().find(|n| n.ray.kind == RayKind::Refraction).unwrap();
+        let hit = refracted.hit.unwrap();
+        assert_eq!(hit.object, w.objects[1].id());
+        assert_eq!((hit.n1, hit.n2), (1.5, 1.0));
+    }
+
+    #[test]
+    /// Every sample of a pixel gets its own tree
+    fn every_sample_gets_its_own_tree() {
+        let w = default_world();
+        let mut s = render_settings();
+        s.samples = 3;
+
+        assert_eq!(trace_pixel(&default_camera(), &w, &s, 5, 5).len(), 3);
+    }
+}
@@ -0,0 +1,104 @@
+use ray_tracer_challenge::*;
+
+#[cfg(test)]
+mod denoise {
+    use super::*;
+
+    fn noisy(width: i32, height: i32, value: impl Fn(i32) -> f32) -> Canvas {
+        let mut c = Canvas::new(width, height);
+        for y in 0..height {
+            for x in 0..width {
+                let noise = if (x + y) % 2 == 0 { 0.1 } else { -0.1 };
+                let v = value(x) + noise;
+                c.write_pixel(x, y, color(v, v, v));
+            }
+        }
+        c
+    }
+
+    fn variance(c: &Canvas) -> f32 {
+        let mean = c.pixels.iter().map(|p| p.red).sum::<f32>() / c.pixels.len() as f32;
+        c.pixels.iter().map(|p| (p.red - mean).powi(2)).sum::<f32>() / c.pixels.len() as f32
+    }
+
+    fn split_guides(width: i32, height: i32) -> Aovs {
+        let mut albedo = Canvas::new(width, height);
+        let mut normal = Canvas::new(width, height);
+        for y in 0..height {
+            for x in 0..width {
+                let a = if x < width / 2 { 0.0 } else { 1.0 };
+                albedo.write_pixel(x, y, color(a, a, a));
+                normal.write_pixel(x, y, color(0.0, 0.0, -1.0));
+            }
+        }
+        Aovs { depth: Canvas::new(width, height), normal, albedo,
+               object_id: Canvas::new(width, height), shadow: Canvas::new(width, height) }
+    }
+
+    #[test]
+    /// A flat image stays the same
+    fn flat_image_stays_the_same() {
+        let mut c = Canvas::new(5, 5);
+        c.pixels = vec![color(0.3, 0.6, 0.9); 25];
+
+        let d = denoise(&c, None, &denoise_settings()).unwrap();
+
+        assert!(d.pixels.iter().all(|p| *p == color(0.3, 0.6, 0.9)));
+    }
+
+    #[test]
+    /// A radius of zero leaves every pixel alone
+    fn zero_radius_leaves_pixels_alone() {
+        let c = noisy(6, 6, |_| 0.5);
+        let mut s = denoise_settings();
+        s.radius = 0;
+
+        assert_eq!(denoise(&c, None, &s).unwrap().pixels, c.pixels);
+    }
+
+    #[test]
+    /// Denoising smooths out noise
+    fn denoising_smooths_out_noise() {
+        let c = noisy(8, 8, |_| 0.5);
+
+        let d = denoise(&c, None, &denoise_settings()).unwrap();
+
+        assert!(variance(&d) < variance(&c) * 0.1);
+        assert!((d.pixel_at(4, 4).red - 0.5).abs() < 0.02);
+    }
+
+    #[test]
+    /// Guide buffers keep an albedo edge sharp
+    fn guides_keep_edges_sharp() {
+        let c = noisy(8, 8, |x| if x < 4 { 0.2 } else { 0.8 });
+        let mut s = denoise_settings();
+        s.color_sigma = 0.0;
+
+        let blurred = denoise(&c, None, &s).unwrap();
+        let guided = denoise(&c, Some(&split_guides(8, 8)), &s).unwrap();
+
+        assert!((guided.pixel_at(3, 4).red - 0.2).abs() < 0.02);
+        assert!((guided.pixel_at(4, 4).red - 0.8).abs() < 0.02);
+        assert!((blurred.pixel_at(3, 4).red - 0.2).abs() > 0.1);
+    }
+
+    #[test]
+    /// A negative radius is treated as zero
+    fn negative_radius_is_treated_as_zero() {
+        let c = noisy(6, 6, |_| 0.5);
+        let mut s = denoise_settings();
+        s.radius = -2;
+
+        assert_eq!(denoise(&c, None, &s).unwrap().pixels, c.pixels);
+    }
+
+    #[test]
+    /// Guides of a different size than the image are an error
+    fn mismatched_guides_are_an_error() {
+        let c = noisy(8, 8, |_| 0.5);
+
+        let result = denoise(&c, Some(&split_guides(4, 8)), &denoise_settings());
+
+        assert_eq!(result.err(), Some("The normal guide is 4x8 but the image is 8x8".to_string()));
+    }
+}
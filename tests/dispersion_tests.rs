@@ -0,0 +1,176 @@
+use ray_tracer_challenge::*;
+
+mod common;
+use common::*;
+
+#[cfg(test)]
+mod dispersion {
+    use std::ops::Deref;
+    use super::*;
+
+    #[test]
+    /// Materials are not dispersive by default
+    fn materials_are_not_dispersive_by_default() {
+        let m = material();
+
+        assert_eq!(m.dispersion, 0.0);
+        for wavelength in RGB_WAVELENGTHS {
+            assert_eq!(refractive_index_at(1.5, 0.0, wavelength), 1.5);
+        }
+    }
+
+    #[test]
+    /// Shorter wavelengths are bent more than longer ones
+    fn shorter_wavelengths_are_bent_more() {
+        let red = refractive_index_at(1.5, 0.01, RGB_WAVELENGTHS[0]);
+        let green = refractive_index_at(1.5, 0.01, RGB_WAVELENGTHS[1]);
+        let blue = refractive_index_at(1.5, 0.01, RGB_WAVELENGTHS[2]);
+
+        assert!((red - 1.49061).abs() < EPS);
+        assert_eq!(green, 1.5);
+        assert!((blue - 1.51632).abs() < EPS);
+    }
+
+    #[test]
+    /// Computations carry the dispersion of the materials on both sides of the hit
+    fn computations_carry_dispersion_of_both_sides() {
+        let mut s = glass_sphere();
+        s.mut_material().dispersion = 0.02;
+        let r = ray(point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0));
+        let xs = [intersection(4.0, s.deref()), intersection(6.0, s.deref())].to_vec();
+
+        let entering = prepare_computations(xs[0], r, &xs);
+        let leaving = prepare_computations(xs[1], r, &xs);
+
+        assert_eq!(entering.n1_dispersion, 0.0);
+        assert_eq!(entering.n2_dispersion, 0.02);
+        assert_eq!(leaving.n1_dispersion, 0.02);
+        assert_eq!(leaving.n2_dispersion, 0.0);
+    }
+
+    #[test]
+    /// A dispersive material refracts each color channel in a different direction
+    fn dispersive_material_splits_color_channels() {
+        let mut w = default_world();
+        let a = &mut w.objects[0];
+        a.mut_material().ambient = 1.0;
+        a.mut_material().pattern = Some(Box::new(test_pattern()));
+
+        let b = &mut w.objects[1];
+        b.mut_material().transparency = 1.0;
+        b.mut_material().refractive_index = 1.5;
+        b.mut_material().dispersion = 0.05;
+
+        let r = ray(point(0.0, 0.0, 0.1), vector(0.0, 1.0, 0.0));
+        let xs = [intersection(-0.9899, w.objects[0].deref()), intersection(-0.4899, w.objects[1].deref()),
+                               intersection(0.4899, w.objects[1].deref()), intersection(0.9899, w.objects[0].deref())].to_vec();
+
+        let comps = prepare_computations(xs[2], r, &xs);
+
+        let c = refracted_color(&w, &comps, 5);
+
+        assert_eq!(c.red, 0.0);
+        assert!((c.green - 0.99878).abs() < EPS);
+        assert!((c.blue - 0.03835).abs() < EPS);
+    }
+
+    #[test]
+    /// A dispersive slab in front of a uniform background stays colorless
+    fn dispersive_slab_over_uniform_background_stays_colorless() {
+        let mut w = world();
+        w.background = Background::Solid(color(1.0, 1.0, 1.0));
+        let mut slab = cube();
+        slab.set_transform(rotation_y(0.5) * scaling(2.0, 2.0, 0.5));
+        let m = slab.mut_material();
+        m.color = color(0.0, 0.0, 0.0);
+        m.ambient = 0.0;
+        m.diffuse = 0.0;
+        m.specular = 0.0;
+        m.transparency = 1.0;
+        m.refractive_index = 1.5;
+        m.dispersion = 0.05;
+        w.objects.push(slab);
+        let r = ray(point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0));
+
+        let c = color_at(&w, r, DEFAULT_REFLECTION_NUMBER);
+
+        assert!((c.red - c.green).abs() < EPS);
+        assert!((c.green - c.blue).abs() < EPS);
+    }
+
+    fn dispersive_slab() -> World {
+        let mut w = world();
+        w.background = Background::Solid(color(1.0, 1.0, 1.0));
+        let mut slab = cube();
+        slab.set_transform(rotation_y(0.5) * scaling(2.0, 2.0, 0.5));
+        let m = slab.mut_material();
+        m.color = color(0.0, 0.0, 0.0);
+        m.ambient = 0.0;
+        m.diffuse = 0.0;
+        m.specular = 0.0;
+        m.transparency = 1.0;
+        m.refractive_index = 1.5;
+        m.dispersion = 0.05;
+        w.objects.push(slab);
+        w
+    }
+
+    fn channels_below(node: &RayNode) -> Vec<Option<usize>> {
+        node.children.iter()
+            .filter(|c| c.ray.kind != RayKind::Shadow)
+            .flat_map(|c| std::iter::once(c.ray.channel).chain(channels_below(c)))
+            .collect()
+    }
+
+    #[test]
+    /// A ray carrying a single channel sees the refractive index of its wavelength
+    fn single_channel_ray_sees_the_index_of_its_wavelength() {
+        let mut s = glass_sphere();
+        s.mut_material().dispersion = 0.01;
+        let mut r = ray(point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0));
+        r.channel = Some(0);
+        let xs = [intersection(4.0, s.deref()), intersection(6.0, s.deref())].to_vec();
+
+        let comps = prepare_computations(xs[0], r, &xs);
+
+        assert_eq!(comps.channel, Some(0));
+        assert!((comps.n2 - 1.49061).abs() < EPS);
+        assert_eq!(transform(r, translation(1.0, 0.0, 0.0)).channel, Some(0));
+    }
+
+    #[test]
+    /// Rays split by dispersion stay monochromatic and are not split again
+    fn split_rays_are_not_split_again() {
+        let w = dispersive_slab();
+
+        let trees = trace_pixel(&default_camera(), &w, &render_settings(), 5, 5);
+
+        let split = trees[0].children.iter().filter(|c| c.ray.kind == RayKind::Refraction).collect::<Vec<_>>();
+        assert_eq!(split.iter().map(|c| c.ray.channel).collect::<Vec<_>>(), vec![Some(0), Some(1), Some(2)]);
+        for node in split {
+            assert!(channels_below(node).iter().all(|c| *c == node.ray.channel));
+            assert!(node.children.iter().filter(|c| c.ray.kind == RayKind::Refraction).count() <= 1);
+        }
+    }
+
+    #[test]
+    /// The path tracer follows one channel through a dispersive surface
+    fn path_tracer_follows_one_channel_through_dispersion() {
+        let w = dispersive_slab();
+        let r = ray(point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0));
+
+        let n = 600;
+        let mut total = color(0.0, 0.0, 0.0);
+        for i in 0..n {
+            seed_random(i);
+            let c = path_trace(&w, r, 8);
+            let lit = [c.red, c.green, c.blue].iter().filter(|v| **v > 0.0).count();
+            assert!(lit <= 1);
+            total = total + c;
+        }
+        let mean = total * (1.0 / n as f32);
+
+        assert!((mean.red - mean.green).abs() < 0.2);
+        assert!((mean.green - mean.blue).abs() < 0.2);
+    }
+}
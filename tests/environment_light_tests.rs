@@ -0,0 +1,122 @@
+use std::f32::consts::PI;
+use std::ops::Deref;
+use ray_tracer_challenge::*;
+
+#[cfg(test)]
+mod environment_light {
+    use super::*;
+
+    fn lit_floor(background: Background, samples: u32) -> World {
+        let mut w = world();
+        w.background = background;
+        w.environment_light = Some(environment_light(samples));
+        let mut floor = plane();
+        floor.mut_material().ambient = 0.0;
+        floor.mut_material().diffuse = 0.8;
+        w.objects.push(floor);
+        w
+    }
+
+    fn shade_floor(w: &World) -> Color {
+        let r = ray(point(0.0, 1.0, 0.0), vector(0.0, -1.0, 0.0));
+        let xs = [intersection(1.0, w.objects[0].deref())].to_vec();
+        let comps = prepare_computations(xs[0], r, &xs);
+        shade_hit(w, &comps, DEFAULT_REFLECTION_NUMBER)
+    }
+
+    fn close(a: Color, b: Color, tolerance: f32) -> bool {
+        (a.red - b.red).abs() < tolerance &&
+            (a.green - b.green).abs() < tolerance &&
+            (a.blue - b.blue).abs() < tolerance
+    }
+
+    #[test]
+    /// Luminance weights the channels by perceived brightness
+    fn luminance_weights_the_channels() {
+        assert_eq!(luminance(color(1.0, 1.0, 1.0)), 1.0);
+        assert!(luminance(color(0.0, 1.0, 0.0)) > luminance(color(1.0, 0.0, 0.0)));
+        assert!(luminance(color(1.0, 0.0, 0.0)) > luminance(color(0.0, 0.0, 1.0)));
+    }
+
+    #[test]
+    /// Cosine-weighted samples stay in the hemisphere around the normal
+    fn cosine_samples_stay_in_the_hemisphere() {
+        let normal = normalize(vector(1.0, 1.0, 0.0));
+
+        for _ in 0..100 {
+            let d = sample_hemisphere_cosine(normal);
+            assert!(dot(d, normal) >= 0.0);
+            assert!((magnitude(d) - 1.0).abs() < EPS);
+        }
+    }
+
+    #[test]
+    /// A uniform sky lights a diffuse floor with its own color
+    fn uniform_sky_lights_a_diffuse_floor() {
+        let w = lit_floor(Background::Solid(color(0.5, 0.25, 1.0)), 16);
+
+        assert_eq!(shade_floor(&w), color(0.4, 0.2, 0.8));
+    }
+
+    #[test]
+    /// A scene without point lights is dark without environment lighting
+    fn scene_without_lights_is_dark_without_environment_lighting() {
+        let mut w = lit_floor(Background::Solid(color(1.0, 1.0, 1.0)), 16);
+        w.environment_light = None;
+
+        assert_eq!(shade_floor(&w), color(0.0, 0.0, 0.0));
+    }
+
+    #[test]
+    /// Importance samples of a uniform map integrate to the full sphere
+    fn importance_samples_integrate_to_the_full_sphere() {
+        let mut image = Canvas::new(16, 8);
+        image.pixels.iter_mut().for_each(|p| *p = color(1.0, 1.0, 1.0));
+        let map = environment_map(image).unwrap();
+
+        let n = 2000;
+        let estimate = (0..n).map(|_| {
+            let (_, radiance, pdf) = sample_environment(&map);
+            radiance.red / pdf
+        }).sum::<f32>() / n as f32;
+
+        assert!((estimate - 4.0 * PI).abs() < 0.05 * 4.0 * PI);
+    }
+
+    #[test]
+    /// Importance sampling favours the bright parts of the map
+    fn importance_sampling_favours_bright_texels() {
+        let mut image = Canvas::new(8, 4);
+        image.pixels.iter_mut().for_each(|p| *p = color(0.01, 0.01, 0.01));
+        image.write_pixel(4, 1, color(100.0, 100.0, 100.0));
+        let map = environment_map(image).unwrap();
+
+        let bright = (0..200)
+            .map(|_| sample_environment(&map).1)
+            .filter(|c| c.red > 1.0)
+            .count();
+
+        assert!(bright > 180);
+    }
+
+    #[test]
+    /// An environment map lights a diffuse floor
+    fn environment_map_lights_a_diffuse_floor() {
+        let mut image = Canvas::new(16, 8);
+        image.pixels.iter_mut().for_each(|p| *p = color(0.5, 0.5, 0.5));
+        let w = lit_floor(Background::Environment(environment_map(image).unwrap()), 512);
+
+        assert!(close(shade_floor(&w), color(0.4, 0.4, 0.4), 0.03));
+    }
+
+    #[test]
+    /// Objects block the environment light
+    fn objects_block_the_environment_light() {
+        let mut w = lit_floor(Background::Solid(color(1.0, 1.0, 1.0)), 256);
+        let mut cover = plane();
+        cover.set_transform(translation(0.0, 2.0, 0.0));
+        w.objects.push(cover);
+
+        assert_eq!(shade_floor(&w), color(0.0, 0.0, 0.0));
+    }
+}
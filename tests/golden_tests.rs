@@ -0,0 +1,88 @@
+use ray_tracer_challenge::*;
+
+// Renders the chapter scenes at a small size and checks them against the
+// reference images in tests/golden. Run with UPDATE_GOLDEN=1 to rewrite them.
+#[cfg(test)]
+mod golden {
+    use std::fs;
+    use super::*;
+
+    const WIDTH: i32 = 64;
+    const HEIGHT: i32 = 48;
+    const TOLERANCE: f32 = 0.01;
+
+    fn golden_path(name: &str) -> String {
+        format!("{}/tests/golden/{name}.ppm", env!("CARGO_MANIFEST_DIR"))
+    }
+
+    fn render_small(world: &World) -> Canvas {
+        render(&chapter_camera(WIDTH, HEIGHT), world)
+    }
+
+    fn assert_matches_golden(name: &str, image: Canvas) {
+        // The reference images are PPMs, which clamp every channel to [0, 1]
+        let image = image.map(|c| color(c.red.clamp(0.0, 1.0), c.green.clamp(0.0, 1.0), c.blue.clamp(0.0, 1.0)));
+        let path = golden_path(name);
+        if std::env::var_os("UPDATE_GOLDEN").is_some() {
+            fs::write(&path, canvas_to_ppm(image)).unwrap();
+            return;
+        }
+
+        let expected = load_ppm(&path).unwrap_or_else(|e| panic!("{e}; run with UPDATE_GOLDEN=1 to create it"));
+        let diff = compare_images(&expected, &image).unwrap();
+        if diff.rmse > TOLERANCE {
+            let actual = format!("{}/{name}-actual.ppm", env!("CARGO_TARGET_TMPDIR"));
+            fs::write(&actual, canvas_to_ppm(image)).unwrap();
+            panic!("{name} differs from {path} by rmse {} (max {}); rendered image written to {actual}", diff.rmse, diff.max_diff);
+        }
+    }
+
+    #[test]
+    /// The chapter 4 clock matches its reference image
+    fn chapter4_matches_golden_image() {
+        assert_matches_golden("chapter4", chapter4_clock(HEIGHT));
+    }
+
+    #[test]
+    /// The chapter 5 silhouette matches its reference image
+    fn chapter5_matches_golden_image() {
+        assert_matches_golden("chapter5", chapter5_silhouette(HEIGHT));
+    }
+
+    #[test]
+    /// The chapter 6 shaded sphere matches its reference image
+    fn chapter6_matches_golden_image() {
+        assert_matches_golden("chapter6", chapter6_sphere(HEIGHT));
+    }
+
+    #[test]
+    /// The chapter 8 scene matches its reference image
+    fn chapter8_matches_golden_image() {
+        assert_matches_golden("chapter8", render_small(&chapter8_world()));
+    }
+
+    #[test]
+    /// The chapter 11 scene matches its reference image
+    fn chapter11_matches_golden_image() {
+        assert_matches_golden("chapter11", render_small(&chapter11_world()));
+    }
+
+    #[test]
+    /// A quarter turn of the chapter 11 turntable matches its reference image
+    fn turntable_matches_golden_image() {
+        let mut world = chapter11_world();
+        let mut camera = chapter_camera(WIDTH, HEIGHT);
+        chapter11_turntable().apply(1.0, &mut camera, &mut world).unwrap();
+
+        assert_matches_golden("turntable", render(&camera, &world));
+    }
+
+    #[test]
+    /// The tolerance is tight enough to tell the chapter scenes apart
+    fn golden_images_catch_scene_changes() {
+        let chapter8 = load_ppm(&golden_path("chapter8")).unwrap();
+        let chapter11 = load_ppm(&golden_path("chapter11")).unwrap();
+
+        assert!(compare_images(&chapter8, &chapter11).unwrap().rmse > TOLERANCE);
+    }
+}
@@ -0,0 +1,112 @@
+use ray_tracer_challenge::*;
+
+#[cfg(test)]
+mod image_diff {
+    use super::*;
+
+    fn filled(width: i32, height: i32, c: Color) -> Canvas {
+        let mut canvas = Canvas::new(width, height);
+        canvas.pixels = vec![c; (width * height) as usize];
+        canvas
+    }
+
+    #[test]
+    /// Identical images have no error
+    fn identical_images_have_no_error() {
+        let a = filled(3, 2, color(0.2, 0.4, 0.6));
+
+        let d = compare_images(&a, &a.clone()).unwrap();
+
+        assert_eq!(d.rmse, 0.0);
+        assert_eq!(d.max_diff, 0.0);
+        assert_eq!(d.psnr, f32::INFINITY);
+        assert!(d.heat_map.pixels.iter().all(|p| *p == color(0.0, 0.0, 0.0)));
+    }
+
+    #[test]
+    /// Error metrics of a single changed pixel
+    fn error_metrics_of_a_changed_pixel() {
+        let a = filled(2, 2, color(0.5, 0.5, 0.5));
+        let mut b = a.clone();
+        b.write_pixel(1, 0, color(0.5, 0.5, 0.9));
+
+        let d = compare_images(&a, &b).unwrap();
+
+        assert!((d.rmse - 0.11547).abs() < EPS);
+        assert!((d.psnr - 18.75061).abs() < 0.001);
+        assert!((d.max_diff - 0.4).abs() < EPS);
+    }
+
+    #[test]
+    /// The heat map grows from black through red and yellow to white
+    fn heat_map_shows_where_images_differ() {
+        let a = filled(4, 1, color(0.0, 0.0, 0.0));
+        let mut b = a.clone();
+        b.write_pixel(1, 0, color(0.2, 0.0, 0.0));
+        b.write_pixel(2, 0, color(0.0, 0.5, 0.0));
+        b.write_pixel(3, 0, color(0.0, 0.0, 1.0));
+
+        let heat = compare_images(&a, &b).unwrap().heat_map;
+
+        assert_eq!(heat.pixel_at(0, 0), color(0.0, 0.0, 0.0));
+        assert_eq!(heat.pixel_at(1, 0), color(0.6, 0.0, 0.0));
+        assert_eq!(heat.pixel_at(2, 0), color(1.0, 0.5, 0.0));
+        assert_eq!(heat.pixel_at(3, 0), color(1.0, 1.0, 1.0));
+    }
+
+    #[test]
+    /// Images of different sizes cannot be compared
+    fn images_of_different_sizes_cannot_be_compared() {
+        assert!(compare_images(&Canvas::new(2, 2), &Canvas::new(2, 3)).is_err());
+    }
+}
+
+#[cfg(test)]
+mod imgdiff {
+    use std::fs;
+    use std::path::{Path, PathBuf};
+    use std::process::Command;
+    use super::*;
+
+    fn write_image(name: &str, c: Color) -> PathBuf {
+        let mut canvas = Canvas::new(2, 2);
+        canvas.pixels = vec![c; 4];
+        let path = Path::new(env!("CARGO_TARGET_TMPDIR")).join(format!("imgdiff-{name}.ppm"));
+        fs::write(&path, canvas_to_ppm(canvas)).unwrap();
+        path
+    }
+
+    fn imgdiff(args: &[&str]) -> Option<i32> {
+        Command::new(env!("CARGO_BIN_EXE_imgdiff")).args(args).output().unwrap().status.code()
+    }
+
+    #[test]
+    /// imgdiff exits with 0 within the tolerance, 1 beyond it and 2 on errors
+    fn imgdiff_exit_codes() {
+        let a = write_image("a", color(0.5, 0.5, 0.5));
+        let b = write_image("b", color(0.6, 0.5, 0.5));
+        let (a, b) = (a.to_str().unwrap(), b.to_str().unwrap());
+
+        assert_eq!(imgdiff(&[a, a]), Some(0));
+        assert_eq!(imgdiff(&[a, b]), Some(1));
+        assert_eq!(imgdiff(&[a, b, "--tolerance", "0.1"]), Some(0));
+        assert_eq!(imgdiff(&[a, b, "--tolerance", "NaN"]), Some(2));
+        assert_eq!(imgdiff(&[a, b, "--tolerance", "inf"]), Some(2));
+        assert_eq!(imgdiff(&[a, b, "--tolerance", "-0.1"]), Some(2));
+        assert_eq!(imgdiff(&[a, "missing.ppm"]), Some(2));
+        assert_eq!(imgdiff(&[a]), Some(2));
+    }
+
+    #[test]
+    /// imgdiff writes the heat map when asked to
+    fn imgdiff_writes_the_heat_map() {
+        let a = write_image("c", color(0.0, 0.0, 0.0));
+        let b = write_image("d", color(1.0, 1.0, 1.0));
+        let out = Path::new(env!("CARGO_TARGET_TMPDIR")).join("imgdiff-heat.ppm");
+
+        imgdiff(&[a.to_str().unwrap(), b.to_str().unwrap(), "--diff", out.to_str().unwrap()]);
+
+        let heat = load_ppm(out.to_str().unwrap()).unwrap();
+        assert_eq!(heat.pixel_at(1, 1), color(1.0, 1.0, 1.0));
+    }
+}
@@ -49,9 +49,9 @@ mod intersection {
         let i1 = intersection(1.0, s.deref());
         let i2 = intersection(2.0, s.deref());
 
-        let mut xs = [i2, i1].to_vec();
+        let xs = [i2, i1].to_vec();
 
-        let i = hit(&mut xs);
+        let i = hit(&xs);
 
         assert_eq!(i.unwrap(), i1);
     }
@@ -63,9 +63,9 @@ mod intersection {
         let i1 = intersection(-1.0, s.deref());
         let i2 = intersection(1.0, s.deref());
 
-        let mut xs = [i2, i1].to_vec();
+        let xs = [i2, i1].to_vec();
 
-        let i = hit(&mut xs);
+        let i = hit(&xs);
 
         assert_eq!(i.unwrap(), i2);
     }
@@ -77,9 +77,9 @@ mod intersection {
         let i1 = intersection(-2.0, s.deref());
         let i2 = intersection(-1.0, s.deref());
 
-        let mut xs = [i2, i1].to_vec();
+        let xs = [i2, i1].to_vec();
 
-        let i = hit(&mut xs);
+        let i = hit(&xs);
 
         assert_eq!(i, None);
     }
@@ -93,9 +93,9 @@ mod intersection {
         let i3 = intersection(-3.0, s.deref());
         let i4 =intersection(2.0, s.deref());
 
-        let mut xs  = [i1, i2, i3, i4].to_vec();
+        let xs  = [i1, i2, i3, i4].to_vec();
 
-        let i = hit(&mut xs);
+        let i = hit(&xs);
 
         assert_eq!(i.unwrap(), i4)
     }
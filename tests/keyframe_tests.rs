@@ -0,0 +1,70 @@
+use std::f32::consts::PI;
+use ray_tracer_challenge::*;
+
+#[cfg(test)]
+mod keyframe {
+    use super::*;
+
+    fn two_keyframe_track() -> KeyframeTrack {
+        let mut track = keyframe_track();
+        track.add(keyframe(2.0,
+                           vector(10.0, 0.0, 0.0),
+                           rotation_quaternion(vector(0.0, 1.0, 0.0), PI/2.0),
+                           vector(3.0, 3.0, 3.0)));
+        track.add(keyframe(0.0,
+                           vector(0.0, 0.0, 0.0),
+                           Quaternion::identity(),
+                           vector(1.0, 1.0, 1.0)));
+        track
+    }
+
+    #[test]
+    /// An empty track is the identity transformation
+    fn empty_track_is_identity() {
+        let track = keyframe_track();
+
+        assert_eq!(track.sample(1.0), None);
+        assert_eq!(track.transform_at(1.0), Matrix::identity4x4());
+    }
+
+    #[test]
+    /// Keyframes are kept ordered by time
+    fn keyframes_are_kept_ordered_by_time() {
+        let track = two_keyframe_track();
+
+        assert_eq!(track.keyframes[0].time, 0.0);
+        assert_eq!(track.keyframes[1].time, 2.0);
+    }
+
+    #[test]
+    /// Sampling between keyframes interpolates translation, rotation and scale
+    fn sampling_between_keyframes() {
+        let track = two_keyframe_track();
+
+        let k = track.sample(1.0).unwrap();
+
+        assert_eq!(k.translation, vector(5.0, 0.0, 0.0));
+        assert_eq!(k.scale, vector(2.0, 2.0, 2.0));
+        assert_eq!(quaternion_to_matrix(k.rotation), rotation_y(PI/4.0));
+        assert_eq!(track.transform_at(1.0),
+                   translation(5.0, 0.0, 0.0) * rotation_y(PI/4.0) * scaling(2.0, 2.0, 2.0));
+    }
+
+    #[test]
+    /// Sampling outside of the track holds the first and last keyframe
+    fn sampling_outside_of_the_track() {
+        let track = two_keyframe_track();
+
+        assert_eq!(track.transform_at(-1.0), Matrix::identity4x4());
+        assert_eq!(track.transform_at(5.0),
+                   translation(10.0, 0.0, 0.0) * rotation_y(PI/2.0) * scaling(3.0, 3.0, 3.0));
+    }
+
+    #[test]
+    /// Sampling at a time that is not a number finds no keyframe
+    fn sampling_at_nan_finds_no_keyframe() {
+        let track = two_keyframe_track();
+
+        assert!(track.sample(f32::NAN).is_none());
+    }
+}
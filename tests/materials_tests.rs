@@ -154,4 +154,57 @@ mod materials {
         assert_eq!(m.transparency, 0.0);
         assert_eq!(m.refractive_index, 1.0);
     }
-}
\ No newline at end of file
+
+    #[test]
+    /// The default material does not emit light
+    fn the_default_material_does_not_emit_light() {
+        let m = material();
+
+        assert_eq!(m.emissive, color(0.0, 0.0, 0.0));
+        assert_eq!(emitted(&m), color(0.0, 0.0, 0.0));
+    }
+
+    #[test]
+    /// Emission is the emissive color scaled by its strength
+    fn emission_is_scaled_by_strength() {
+        let mut m = material();
+        m.emissive = color(1.0, 0.5, 0.25);
+        m.emissive_strength = 4.0;
+
+        assert_eq!(emitted(&m), color(4.0, 2.0, 1.0));
+    }
+
+    #[test]
+    /// An emissive surface glows even without lights
+    fn an_emissive_surface_glows_without_lights() {
+        let mut w = world();
+        let mut s = cube();
+        s.mut_material().emissive = color(1.0, 0.8, 0.6);
+        s.mut_material().emissive_strength = 0.5;
+        w.objects.push(s);
+
+        let r = ray(point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0));
+
+        assert_eq!(color_at(&w, r, DEFAULT_REFLECTION_NUMBER), color(0.5, 0.4, 0.3));
+        assert_eq!(path_trace(&w, r, DEFAULT_PATH_DEPTH), color(0.5, 0.4, 0.3));
+    }
+
+    #[test]
+    /// An emissive surface lights other objects under the path tracer
+    fn an_emissive_surface_lights_other_objects() {
+        let mut w = world();
+        let mut floor = plane();
+        floor.mut_material().ambient = 0.0;
+        w.objects.push(floor);
+        let mut ceiling = plane();
+        ceiling.set_transform(translation(0.0, 2.0, 0.0));
+        ceiling.mut_material().color = color(0.0, 0.0, 0.0);
+        ceiling.mut_material().emissive = color(1.0, 1.0, 1.0);
+        w.objects.push(ceiling);
+
+        let r = ray(point(0.0, 1.0, 0.0), vector(0.0, -1.0, 0.0));
+
+        assert_eq!(color_at(&w, r, DEFAULT_REFLECTION_NUMBER), color(0.0, 0.0, 0.0));
+        assert_eq!(path_trace(&w, r, DEFAULT_PATH_DEPTH), color(0.9, 0.9, 0.9));
+    }
+}
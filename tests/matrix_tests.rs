@@ -303,6 +303,20 @@ mod matrix {
         ));
     }
 
+    #[test]
+    /// Calculating the inverse of a 3x3 matrix
+    fn calculating_the_inverse_of_a_3x3_matrix() {
+        let a = Matrix::new3x3(
+            [2.0, 0.0, 0.0],
+            [0.0, 4.0, 0.0],
+            [1.0, 0.0, 8.0]);
+        let b = inverse(&a);
+        assert_eq!(b, Matrix::new3x3(
+            [0.5, 0.0, 0.0],
+            [0.0, 0.25, 0.0],
+            [-0.0625, 0.0, 0.125]));
+    }
+
     #[test]
     /// Calculating the inverse of another matrix
     fn calculating_the_inverse_of_another_matrix() {
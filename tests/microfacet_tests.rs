@@ -0,0 +1,123 @@
+use std::ops::Deref;
+use ray_tracer_challenge::*;
+
+#[cfg(test)]
+mod microfacet {
+    use super::*;
+
+    fn microfacet_material(base: Color, metallic: f32, roughness: f32) -> Material {
+        let mut m = material();
+        m.shading = Shading::Microfacet;
+        m.color = base;
+        m.metallic = metallic;
+        m.roughness = roughness;
+        m
+    }
+
+    #[test]
+    /// Materials use Phong shading by default
+    fn materials_use_phong_shading_by_default() {
+        let m = material();
+
+        assert_eq!(m.shading, Shading::Phong);
+        assert_eq!(m.metallic, 0.0);
+        assert_eq!(m.roughness, 0.5);
+    }
+
+    #[test]
+    /// A rough dielectric lit head-on
+    fn rough_dielectric_lit_head_on() {
+        let m = microfacet_material(color(1.0, 1.0, 1.0), 0.0, 1.0);
+        let s = sphere();
+        let eye_v = vector(0.0, 0.0, -1.0);
+        let normal_v = vector(0.0, 0.0, -1.0);
+        let light = point_light(point(0.0, 0.0, -10.0), color(1.0, 1.0, 1.0));
+
+        let result = lightning(&m, s.deref(), &light, point(0.0, 0.0, 0.0), eye_v, normal_v, false);
+
+        assert_eq!(result, color(1.07, 1.07, 1.07));
+    }
+
+    #[test]
+    /// A rough metal reflects only with its base color
+    fn rough_metal_reflects_with_its_base_color() {
+        let m = microfacet_material(color(1.0, 0.5, 0.0), 1.0, 1.0);
+        let s = sphere();
+        let eye_v = vector(0.0, 0.0, -1.0);
+        let normal_v = vector(0.0, 0.0, -1.0);
+        let light = point_light(point(0.0, 0.0, -10.0), color(1.0, 1.0, 1.0));
+
+        let result = lightning(&m, s.deref(), &light, point(0.0, 0.0, 0.0), eye_v, normal_v, false);
+
+        assert_eq!(result, color(0.35, 0.175, 0.0));
+    }
+
+    #[test]
+    /// Lighting a microfacet surface from behind leaves only ambient
+    fn lighting_a_microfacet_surface_from_behind() {
+        let m = microfacet_material(color(1.0, 1.0, 1.0), 0.0, 0.5);
+        let s = sphere();
+        let eye_v = vector(0.0, 0.0, -1.0);
+        let normal_v = vector(0.0, 0.0, -1.0);
+        let light = point_light(point(0.0, 0.0, 10.0), color(1.0, 1.0, 1.0));
+
+        let result = lightning(&m, s.deref(), &light, point(0.0, 0.0, 0.0), eye_v, normal_v, false);
+
+        assert_eq!(result, color(0.1, 0.1, 0.1));
+    }
+
+    #[test]
+    /// Smoother surfaces have tighter and brighter highlights
+    fn smoother_surfaces_have_tighter_highlights() {
+        let normal_v = vector(0.0, 1.0, 0.0);
+        let light_v = normalize(vector(-1.0, 1.0, 0.0));
+        let mirror_v = normalize(vector(1.0, 1.0, 0.0));
+        let off_v = normalize(vector(1.0, 3.0, 0.0));
+        let base = color(1.0, 1.0, 1.0);
+        let smooth = microfacet_material(base, 1.0, 0.2);
+        let rough = microfacet_material(base, 1.0, 0.8);
+
+        let (_, smooth_at_mirror) = microfacet_brdf(&smooth, base, normal_v, mirror_v, light_v);
+        let (_, rough_at_mirror) = microfacet_brdf(&rough, base, normal_v, mirror_v, light_v);
+        let (_, smooth_off) = microfacet_brdf(&smooth, base, normal_v, off_v, light_v);
+        let (_, rough_off) = microfacet_brdf(&rough, base, normal_v, off_v, light_v);
+
+        assert!(smooth_at_mirror.red > rough_at_mirror.red);
+        assert!(smooth_off.red < rough_off.red);
+    }
+
+    #[test]
+    /// Fresnel makes dielectrics more reflective at grazing angles
+    fn fresnel_makes_dielectrics_reflective_at_grazing_angles() {
+        let m = microfacet_material(color(0.0, 0.0, 0.0), 0.0, 0.5);
+        let normal_v = vector(0.0, 1.0, 0.0);
+        let head_on = vector(0.0, 1.0, 0.0);
+        let grazing = normalize(vector(1.0, 0.05, 0.0));
+        let grazing_light = normalize(vector(-1.0, 0.05, 0.0));
+
+        let (_, at_normal) = microfacet_brdf(&m, m.color, normal_v, head_on, head_on);
+        let (_, at_grazing) = microfacet_brdf(&m, m.color, normal_v, grazing, grazing_light);
+
+        assert!(at_grazing.red > at_normal.red);
+    }
+
+    #[test]
+    /// A white microfacet object in a uniform sky stays close to the sky color
+    fn white_microfacet_object_in_a_uniform_sky() {
+        let mut w = world();
+        w.background = Background::Solid(color(1.0, 1.0, 1.0));
+        let mut s = sphere();
+        s.set_material(microfacet_material(color(1.0, 1.0, 1.0), 0.0, 1.0));
+        w.objects.push(s);
+        let r = ray(point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0));
+
+        let n = 256;
+        let mut sum = color(0.0, 0.0, 0.0);
+        for _ in 0..n {
+            sum = sum + path_trace(&w, r, DEFAULT_PATH_DEPTH);
+        }
+        let average = sum * (1.0 / n as f32);
+
+        assert!(average.red > 0.8 && average.red < 1.1);
+    }
+}
@@ -0,0 +1,197 @@
+use std::f32::consts::PI;
+use std::ops::Deref;
+use ray_tracer_challenge::*;
+
+#[cfg(test)]
+mod motion_blur {
+    use super::*;
+
+    fn moving_sphere() -> Box<dyn Shape> {
+        let mut s = sphere();
+        s.set_end_transform(Some(translation(4.0, 0.0, 0.0)));
+        s
+    }
+
+    #[test]
+    /// Rays start at time zero and keep their time when transformed
+    fn rays_keep_their_time() {
+        let r = ray(point(1.0, 2.0, 3.0), vector(0.0, 1.0, 0.0));
+        assert_eq!(r.time, 0.0);
+
+        let r = ray_with_time(point(1.0, 2.0, 3.0), vector(0.0, 1.0, 0.0), 0.25);
+        let r2 = transform(r, translation(3.0, 4.0, 5.0));
+
+        assert_eq!(r2.time, 0.25);
+    }
+
+    #[test]
+    /// A static shape has the same transform at any time
+    fn static_shape_has_the_same_transform_at_any_time() {
+        let mut s = sphere();
+        s.set_transform(translation(1.0, 0.0, 0.0));
+
+        assert_eq!(s.end_transform(), None);
+        assert_eq!(s.transform_at(0.7), translation(1.0, 0.0, 0.0));
+    }
+
+    #[test]
+    /// A moving shape interpolates between shutter open and close transforms
+    fn moving_shape_interpolates_transforms() {
+        let mut s = sphere();
+        s.set_transform(rotation_y(0.0));
+        s.set_end_transform(Some(translation(4.0, 0.0, 0.0) * rotation_y(PI/2.0) * scaling(3.0, 3.0, 3.0)));
+
+        assert_eq!(s.transform_at(0.0), Matrix::identity4x4());
+        assert_eq!(s.transform_at(0.5), translation(2.0, 0.0, 0.0) * rotation_y(PI/4.0) * scaling(2.0, 2.0, 2.0));
+        assert_eq!(s.transform_at(1.0), translation(4.0, 0.0, 0.0) * rotation_y(PI/2.0) * scaling(3.0, 3.0, 3.0));
+    }
+
+    #[test]
+    /// A sheared transform is kept exactly at the ends and blended elementwise between them
+    fn sheared_transforms_are_interpolated_elementwise() {
+        let mut s = sphere();
+        s.set_transform(shearing(1.0, 0.0, 0.0, 0.0, 0.0, 0.0));
+        s.set_end_transform(Some(translation(2.0, 0.0, 0.0)));
+
+        assert_eq!(s.transform_at(0.0), shearing(1.0, 0.0, 0.0, 0.0, 0.0, 0.0));
+        assert_eq!(s.transform_at(0.5), Matrix::new4x4(
+            [1.0, 0.5, 0.0, 1.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [0.0, 0.0, 0.0, 1.0]));
+        assert_eq!(s.transform_at(1.0), translation(2.0, 0.0, 0.0));
+    }
+
+    #[test]
+    /// Interpolating towards a zero scale stays finite
+    fn interpolating_towards_a_zero_scale_stays_finite() {
+        let m = interpolate_transform(&Matrix::identity4x4(), &scaling(0.0, 1.0, 1.0), 0.5);
+
+        assert_eq!(m, scaling(0.5, 1.0, 1.0));
+    }
+
+    #[test]
+    /// Intersecting a moving sphere depends on the ray time
+    fn intersecting_a_moving_sphere_depends_on_time() {
+        let s = moving_sphere();
+
+        let early = ray_with_time(point(4.0, 0.0, -5.0), vector(0.0, 0.0, 1.0), 0.0);
+        let late = ray_with_time(point(4.0, 0.0, -5.0), vector(0.0, 0.0, 1.0), 1.0);
+
+        assert_eq!(s.intersect(early).len(), 0);
+        let xs = s.intersect(late);
+        assert_eq!(xs.len(), 2);
+        assert_eq!(xs[0].t, 4.0);
+        assert_eq!(xs[1].t, 6.0);
+    }
+
+    #[test]
+    /// The normal on a moving sphere follows the sphere
+    fn normal_on_a_moving_sphere() {
+        let s = moving_sphere();
+
+        assert_eq!(s.normal_at_time(point(2.0, 1.0, 0.0), 0.5), vector(0.0, 1.0, 0.0));
+    }
+
+    #[test]
+    /// Precomputing an intersection keeps the ray time
+    fn precomputing_an_intersection_keeps_the_ray_time() {
+        let s = moving_sphere();
+        let r = ray_with_time(point(2.0, 0.0, -5.0), vector(0.0, 0.0, 1.0), 0.5);
+        let i = intersection(4.0, s.deref());
+
+        let xs = [i].to_vec();
+
+        let comps = prepare_computations(i, r, &xs);
+
+        assert_eq!(comps.time, 0.5);
+        assert_eq!(comps.normal_v, vector(0.0, 0.0, -1.0));
+    }
+
+    #[test]
+    /// A camera samples time within its shutter interval
+    fn camera_samples_time_across_the_shutter_interval() {
+        let mut c = camera(11, 11, PI/2.0);
+        assert_eq!(c.shutter_open, 0.0);
+        assert_eq!(c.shutter_close, 0.0);
+        assert_eq!(ray_for_pixel_sample(&c, 5, 5, 3).time, 0.0);
+
+        c.shutter_open = 0.25;
+        c.shutter_close = 0.5;
+
+        let times = (0..16).map(|s| ray_for_pixel_sample(&c, 5, 5, s).time).collect::<Vec<_>>();
+
+        assert!(times.iter().all(|t| (0.25..=0.5).contains(t)));
+        assert_eq!(ray_for_pixel(&c, 5, 5).time, 0.25);
+        assert!(times.iter().any(|t| *t != times[0]));
+        assert_eq!(ray_for_pixel_sample(&c, 5, 5, 3).time, times[3]);
+    }
+
+    #[test]
+    /// The first sample goes through the center of the pixel
+    fn first_sample_goes_through_the_center_of_the_pixel() {
+        let c = camera(201, 101, PI/2.0);
+
+        let r = ray_for_pixel_sample(&c, 0, 0, 0);
+
+        assert_eq!(r.direction, ray_for_pixel(&c, 0, 0).direction);
+    }
+
+    fn sphere_moving_across_the_view() -> (World, Camera) {
+        let mut w = world();
+        w.lights.push(point_light(point(-10.0, 10.0, -10.0), color(1.0, 1.0, 1.0)));
+        let mut s = sphere();
+        s.mut_material().ambient = 1.0;
+        s.mut_material().diffuse = 0.0;
+        s.mut_material().specular = 0.0;
+        s.set_transform(translation(-2.0, 0.0, 0.0) * scaling(0.5, 0.5, 0.5));
+        s.set_end_transform(Some(translation(2.0, 0.0, 0.0) * scaling(0.5, 0.5, 0.5)));
+        w.objects.push(s);
+
+        let mut c = camera(11, 1, PI/2.0);
+        c.transform = view_transformation(point(0.0, 0.0, -5.0), point(0.0, 0.0, 0.0), vector(0.0, 1.0, 0.0));
+        (w, c)
+    }
+
+    #[test]
+    /// Rendering a moving sphere blurs it along its path
+    fn rendering_a_moving_sphere_blurs_it() {
+        let (w, mut c) = sphere_moving_across_the_view();
+
+        let sharp = render(&c, &w);
+        assert_eq!(sharp.pixel_at(5, 0), color(0.0, 0.0, 0.0));
+
+        c.shutter_close = 1.0;
+        let mut s = render_settings();
+        s.samples = 32;
+        let blurred = render_with_settings(&c, &w, &s);
+        let center = blurred.pixel_at(5, 0);
+
+        assert!(center.red > 0.05 && center.red < 0.95);
+    }
+
+    #[test]
+    /// A shorter shutter only sees the part of the path it is open for
+    fn shorter_shutter_sees_part_of_the_path() {
+        let (w, mut c) = sphere_moving_across_the_view();
+        c.shutter_close = 0.25;
+        let mut s = render_settings();
+        s.samples = 32;
+
+        let image = render_with_settings(&c, &w, &s);
+
+        assert_eq!(image.pixel_at(5, 0), color(0.0, 0.0, 0.0));
+        assert!(image.pixel_at(3, 0).red > 0.05);
+    }
+
+    #[test]
+    /// Patterns move with their shape
+    fn patterns_move_with_their_shape() {
+        let mut s = sphere();
+        s.set_end_transform(Some(translation(1.0, 0.0, 0.0)));
+        s.mut_material().pattern = Some(Box::new(stripe_pattern(color(1.0, 1.0, 1.0), color(0.0, 0.0, 0.0))));
+
+        assert_eq!(surface_color_at(s.material(), s.as_ref(), point(1.5, 0.0, 0.0), 0.0), color(0.0, 0.0, 0.0));
+        assert_eq!(surface_color_at(s.material(), s.as_ref(), point(1.5, 0.0, 0.0), 1.0), color(1.0, 1.0, 1.0));
+    }
+}
@@ -0,0 +1,140 @@
+use std::f32::consts::PI;
+use ray_tracer_challenge::*;
+
+mod common;
+use common::*;
+
+#[cfg(test)]
+mod path_tracer {
+    use super::*;
+
+    #[test]
+    /// The Whitted integrator renders like render()
+    fn whitted_integrator_renders_like_render() {
+        let w = default_world();
+        let c = default_camera();
+
+        let image = render_with_integrator(&c, &w, Integrator::Whitted);
+
+        assert_eq!(image.pixel_at(5, 5), color(0.38066, 0.47583, 0.2855));
+    }
+
+    #[test]
+    /// A path that misses everything returns the background
+    fn path_that_misses_returns_the_background() {
+        let mut w = default_world();
+        w.background = Background::Solid(color(0.1, 0.2, 0.3));
+        let r = ray(point(0.0, 0.0, -5.0), vector(0.0, 1.0, 0.0));
+
+        assert_eq!(path_trace(&w, r, DEFAULT_PATH_DEPTH), color(0.1, 0.2, 0.3));
+    }
+
+    #[test]
+    /// A diffuse convex object in a uniform sky reflects the sky times its albedo
+    fn diffuse_convex_object_in_a_uniform_sky() {
+        let w = furnace_world();
+        let r = ray(point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0));
+
+        for _ in 0..10 {
+            assert_eq!(path_trace(&w, r, DEFAULT_PATH_DEPTH), color(0.5, 0.25, 1.0));
+        }
+    }
+
+    #[test]
+    /// A path with no bounces left is black
+    fn path_with_no_bounces_left_is_black() {
+        let w = furnace_world();
+        let r = ray(point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0));
+
+        assert_eq!(path_trace(&w, r, 0), color(0.0, 0.0, 0.0));
+    }
+
+    #[test]
+    /// A perfect mirror shows the sky in the path tracer
+    fn perfect_mirror_shows_the_sky() {
+        let mut w = world();
+        w.background = Background::Gradient { top: color(0.0, 0.0, 1.0), bottom: color(0.0, 0.0, 0.0) };
+        let mut floor = plane();
+        floor.mut_material().reflective = 1.0;
+        w.objects.push(floor);
+
+        let r = ray(point(0.0, 1.0, 0.0), vector(0.0, -1.0, 0.0));
+
+        assert_eq!(path_trace(&w, r, DEFAULT_PATH_DEPTH), color(0.0, 0.0, 1.0));
+    }
+
+    #[test]
+    /// Paths between mutually reflective surfaces terminate
+    fn paths_between_mutually_reflective_surfaces_terminate() {
+        let mut w = world();
+        w.lights.push(point_light(point(0.0, 0.0, 0.0), color(1.0, 1.0, 1.0)));
+        let mut lower = plane();
+        lower.mut_material().reflective = 1.0;
+        lower.set_transform(translation(0.0, -1.0, 0.0));
+        w.objects.push(lower);
+        let mut upper = plane();
+        upper.mut_material().reflective = 1.0;
+        upper.set_transform(translation(0.0, 1.0, 0.0));
+        w.objects.push(upper);
+
+        let r = ray(point(0.0, 0.0, 0.0), vector(0.0, 1.0, 0.0));
+
+        assert_eq!(path_trace(&w, r, DEFAULT_PATH_DEPTH), color(0.0, 0.0, 0.0));
+    }
+
+    #[test]
+    /// Light bounced off a colored wall bleeds onto the floor
+    fn colored_wall_bleeds_onto_the_floor() {
+        let mut w = world();
+        w.lights.push(point_light(point(-2.0, 5.0, 0.0), color(1.0, 1.0, 1.0)));
+        let mut floor = plane();
+        floor.mut_material().specular = 0.0;
+        w.objects.push(floor);
+        let mut wall = plane();
+        wall.set_transform(translation(1.0, 0.0, 0.0) * rotation_z(PI/2.0));
+        wall.mut_material().color = color(1.0, 0.0, 0.0);
+        wall.mut_material().specular = 0.0;
+        w.objects.push(wall);
+
+        let r = ray(point(0.5, 1.0, 0.0), vector(0.0, -1.0, 0.0));
+        let whitted = color_at(&w, r, DEFAULT_REFLECTION_NUMBER);
+        assert_eq!(whitted.red, whitted.green);
+
+        let n = 64;
+        let mut sum = color(0.0, 0.0, 0.0);
+        for _ in 0..n {
+            sum = sum + path_trace(&w, r, DEFAULT_PATH_DEPTH);
+        }
+        let traced = sum * (1.0 / n as f32);
+
+        assert!(traced.red > traced.green + 0.05);
+    }
+
+    #[test]
+    /// Rendering with the path tracer averages the samples of each pixel
+    fn rendering_with_the_path_tracer() {
+        let w = furnace_world();
+        let mut c = camera(5, 5, PI/3.0);
+        c.transform = view_transformation(point(0.0, 0.0, -5.0), point(0.0, 0.0, 0.0), vector(0.0, 1.0, 0.0));
+        let mut s = render_settings();
+        s.integrator = Integrator::PathTracer;
+        s.samples = 4;
+
+        let image = render_with_settings(&c, &w, &s);
+
+        assert_eq!(image.pixel_at(2, 2), color(0.5, 0.25, 1.0));
+        assert_eq!(image.pixel_at(0, 0), color(1.0, 1.0, 1.0));
+    }
+
+    #[test]
+    /// The path tracer takes its depth from the render settings
+    fn path_tracer_takes_its_depth_from_the_settings() {
+        let w = furnace_world();
+        let c = default_camera();
+        let mut s = render_settings();
+        s.integrator = Integrator::PathTracer;
+        s.max_path_depth = 0;
+
+        assert_eq!(render_with_settings(&c, &w, &s).pixel_at(5, 5), color(0.0, 0.0, 0.0));
+    }
+}
@@ -0,0 +1,94 @@
+use std::f32::consts::PI;
+use std::time::Duration;
+use ray_tracer_challenge::*;
+
+mod common;
+use common::*;
+
+#[cfg(test)]
+mod progressive {
+    use super::*;
+
+    fn path_traced(samples: u32) -> (Camera, RenderSettings) {
+        let mut c = camera(9, 9, PI / 3.0);
+        c.transform = view_transformation(point(0.0, 0.0, -5.0), point(0.0, 0.0, 0.0), vector(0.0, 1.0, 0.0));
+        let mut s = render_settings();
+        s.samples = samples;
+        s.integrator = Integrator::PathTracer;
+        (c, s)
+    }
+
+    #[test]
+    /// Every pass is reported until the sample budget is spent
+    fn every_pass_is_reported() {
+        let w = furnace_world();
+        let (c, s) = path_traced(4);
+        let mut passes = vec![];
+
+        render_progressive(&c, &w, &s, None, |pass, image| {
+            passes.push(pass);
+            assert_eq!((image.width, image.height), (9, 9));
+            true
+        });
+
+        assert_eq!(passes, vec![1, 2, 3, 4]);
+    }
+
+    #[test]
+    /// The final pass matches a render with the same number of samples
+    fn final_pass_matches_a_full_render() {
+        let w = furnace_world();
+        let (c, s) = path_traced(4);
+
+        let progressive = render_progressive(&c, &w, &s, None, |_, _| true);
+        let full = render_with_settings(&c, &w, &s);
+
+        assert_eq!(progressive.pixels, full.pixels);
+    }
+
+    #[test]
+    /// Every intermediate image is the average of the passes so far
+    fn intermediate_images_average_the_passes_so_far() {
+        let w = furnace_world();
+        let (c, s) = path_traced(3);
+        let mut images = vec![];
+
+        render_progressive(&c, &w, &s, None, |_, image| {
+            images.push(image.pixels.clone());
+            true
+        });
+
+        assert_eq!(images[1], render_with_settings(&c, &w, &path_traced(2).1).pixels);
+    }
+
+    #[test]
+    /// Returning false from the callback stops after the current pass
+    fn returning_false_stops_refinement() {
+        let w = furnace_world();
+        let (c, s) = path_traced(8);
+        let mut passes = 0;
+
+        let image = render_progressive(&c, &w, &s, None, |_, _| {
+            passes += 1;
+            false
+        });
+
+        assert_eq!(passes, 1);
+        assert_eq!(image.pixels, render_with_settings(&c, &w, &path_traced(1).1).pixels);
+    }
+
+    #[test]
+    /// An exhausted time budget stops after the first pass
+    fn exhausted_time_budget_stops_refinement() {
+        let w = furnace_world();
+        let (c, s) = path_traced(8);
+        let mut passes = 0;
+
+        render_progressive(&c, &w, &s, Some(Duration::ZERO), |_, _| {
+            passes += 1;
+            true
+        });
+
+        assert_eq!(passes, 1);
+    }
+}
@@ -0,0 +1,89 @@
+use std::f32::consts::PI;
+use ray_tracer_challenge::*;
+
+#[cfg(test)]
+mod quaternion {
+    use super::*;
+
+    #[test]
+    /// The identity quaternion is the identity rotation
+    fn identity_quaternion_is_the_identity_rotation() {
+        let q = Quaternion::identity();
+
+        assert_eq!(quaternion_to_matrix(q), Matrix::identity4x4());
+    }
+
+    #[test]
+    /// A quaternion around an axis matches the Euler rotation matrices
+    fn quaternion_around_an_axis_matches_euler_rotations() {
+        let qx = rotation_quaternion(vector(1.0, 0.0, 0.0), PI/3.0);
+        let qy = rotation_quaternion(vector(0.0, 1.0, 0.0), PI/4.0);
+        let qz = rotation_quaternion(vector(0.0, 0.0, 1.0), PI/6.0);
+
+        assert_eq!(quaternion_to_matrix(qx), rotation_x(PI/3.0));
+        assert_eq!(quaternion_to_matrix(qy), rotation_y(PI/4.0));
+        assert_eq!(quaternion_to_matrix(qz), rotation_z(PI/6.0));
+    }
+
+    #[test]
+    /// Converting a rotation matrix to a quaternion
+    fn converting_a_rotation_matrix_to_a_quaternion() {
+        let m = rotation_y(PI/2.0);
+
+        let q = matrix_to_quaternion(&m);
+
+        assert_eq!(q, quaternion((PI/4.0).cos(), 0.0, (PI/4.0).sin(), 0.0));
+    }
+
+    #[test]
+    /// A composed rotation survives a round trip through a quaternion
+    fn composed_rotation_survives_a_round_trip() {
+        let m = rotation_x(PI) * rotation_y(PI/3.0) * rotation_z(-PI/5.0);
+
+        let q = matrix_to_quaternion(&m);
+
+        assert_eq!(quaternion_to_matrix(q), m);
+    }
+
+    #[test]
+    /// Multiplying quaternions composes rotations
+    fn multiplying_quaternions_composes_rotations() {
+        let a = rotation_quaternion(vector(1.0, 0.0, 0.0), PI/2.0);
+        let b = rotation_quaternion(vector(0.0, 0.0, 1.0), PI/2.0);
+
+        assert_eq!(quaternion_to_matrix(a * b), rotation_x(PI/2.0) * rotation_z(PI/2.0));
+    }
+
+    #[test]
+    /// Slerp returns the end points and the halfway rotation
+    fn slerp_interpolates_between_rotations() {
+        let a = Quaternion::identity();
+        let b = rotation_quaternion(vector(0.0, 1.0, 0.0), PI/2.0);
+
+        assert_eq!(slerp(a, b, 0.0), a);
+        assert_eq!(slerp(a, b, 1.0), b);
+        assert_eq!(quaternion_to_matrix(slerp(a, b, 0.5)), rotation_y(PI/4.0));
+    }
+
+    #[test]
+    /// Slerp takes the shortest path
+    fn slerp_takes_the_shortest_path() {
+        let a = rotation_quaternion(vector(0.0, 1.0, 0.0), 0.1);
+        let b = rotation_quaternion(vector(0.0, 1.0, 0.0), 0.3);
+        let negated_b = quaternion(-b.w, -b.x, -b.y, -b.z);
+
+        assert_eq!(quaternion_to_matrix(slerp(a, negated_b, 0.5)), rotation_y(0.2));
+    }
+
+    #[test]
+    /// Decomposing a transformation into translation, rotation and scale
+    fn decomposing_a_transformation() {
+        let m = translation(1.0, -2.0, 3.0) * rotation_z(PI/3.0) * scaling(2.0, 0.5, 4.0);
+
+        let (t, r, s) = decompose(&m);
+
+        assert_eq!(t, vector(1.0, -2.0, 3.0));
+        assert_eq!(quaternion_to_matrix(r), rotation_z(PI/3.0));
+        assert_eq!(s, vector(2.0, 0.5, 4.0));
+    }
+}
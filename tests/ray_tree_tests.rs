@@ -0,0 +1,112 @@
+use std::f32::consts::PI;
+use ray_tracer_challenge::*;
+
+mod common;
+use common::*;
+
+#[cfg(test)]
+mod ray_tree {
+    use super::*;
+
+    fn leaf(r: Ray) -> RayNode {
+        let black = color(0.0, 0.0, 0.0);
+        RayNode { ray: r, hit: None, color: black, surface: black, reflected: black, refracted: black, children: vec![] }
+    }
+
+    #[test]
+    /// An opaque hit gets all of its color from the surface
+    fn opaque_hit_gets_its_color_from_the_surface() {
+        let w = default_world();
+
+        let root = &trace_pixel(&default_camera(), &w, &render_settings(), 5, 5)[0];
+
+        assert_eq!(root.surface, root.color);
+        assert_eq!(root.reflected, color(0.0, 0.0, 0.0));
+        assert_eq!(root.refracted, color(0.0, 0.0, 0.0));
+    }
+
+    #[test]
+    /// The contributions of a reflective hit add up to its color
+    fn contributions_add_up_to_the_color() {
+        let mut w = default_world();
+        let mut floor = plane();
+        floor.mut_material().reflective = 0.5;
+        floor.set_transform(translation(0.0, -1.0, 0.0));
+        w.objects.push(floor);
+        let mut c = camera(11, 11, PI / 2.0);
+        c.transform = view_transformation(point(0.0, 0.0, -3.0), point(0.0, -1.0, -2.0), vector(0.0, 1.0, 0.0));
+
+        let root = &trace_pixel(&c, &w, &render_settings(), 5, 5)[0];
+
+        assert_eq!(root.color, color(0.87677, 0.92436, 0.82918));
+        assert_eq!(root.reflected, color(0.19032, 0.2379, 0.14274));
+        assert_eq!(root.surface + root.reflected + root.refracted, root.color);
+    }
+
+    #[test]
+    /// Every bounce of a traced path records what it added itself
+    fn path_bounces_record_their_own_contributions() {
+        let w = furnace_world();
+        let mut s = render_settings();
+        s.integrator = Integrator::PathTracer;
+
+        let root = &trace_pixel(&default_camera(), &w, &s, 5, 5)[0];
+
+        assert_eq!(root.color, color(0.5, 0.25, 1.0));
+        assert_eq!(root.surface, color(0.0, 0.0, 0.0));
+        assert_eq!(root.reflected, root.color);
+        assert_eq!(root.children.len(), 1);
+        let sky = &root.children[0];
+        assert_eq!(sky.ray.kind, RayKind::Reflection);
+        assert!(sky.hit.is_none());
+        assert_eq!(sky.color, color(1.0, 1.0, 1.0));
+        assert_eq!(sky.surface, sky.color);
+    }
+
+    #[test]
+    /// Rays traced outside trace_pixel are not recorded
+    fn rays_outside_trace_pixel_are_not_recorded() {
+        let w = default_world();
+        let c = default_camera();
+
+        render(&c, &w);
+
+        assert_eq!(trace_pixel(&c, &w, &render_settings(), 5, 5).len(), 1);
+    }
+
+    #[test]
+    /// A ray without a hit is written as a JSON leaf
+    fn ray_without_hit_is_a_json_leaf() {
+        let node = leaf(ray(point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0)));
+
+        assert_eq!(ray_tree_to_json(&[node]),
+                   "[{\"kind\":\"camera\",\"origin\":[0,0,-5],\"direction\":[0,0,1],\"time\":0,\"hit\":null,\
+                   \"color\":[0,0,0],\"surface\":[0,0,0],\"reflected\":[0,0,0],\"refracted\":[0,0,0],\"children\":[]}]");
+    }
+
+    #[test]
+    /// Hits and children are nested in the JSON dump
+    fn hits_and_children_are_nested_in_json() {
+        let s = glass_sphere();
+        let mut node = leaf(ray(point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0)));
+        node.hit = Some(RayHit { object: s.id(), t: 4.0, point: point(0.0, 0.0, -1.0), normal_v: vector(0.0, 0.0, -1.0),
+                                 inside: false, n1: 1.0, n2: 1.5 });
+        node.children.push(leaf(ray_of_kind(point(0.0, 0.0, -1.0), vector(0.0, 0.0, 1.0), 0.0, RayKind::Refraction)));
+
+        let json = ray_tree_to_json(&[node]);
+
+        assert!(json.contains(&format!("\"hit\":{{\"object\":\"{}\",\"t\":4,", s.id())));
+        assert!(json.contains("\"inside\":false,\"n1\":1,\"n2\":1.5}"));
+        assert!(json.contains("\"children\":[{\"kind\":\"refraction\""));
+        assert!(json.ends_with("\"children\":[]}]}]"));
+    }
+
+    #[test]
+    /// Numbers JSON cannot represent are written as null
+    fn non_finite_numbers_are_null() {
+        let mut node = leaf(ray(point(0.0, 0.0, 0.0), vector(0.0, 0.0, 1.0)));
+        node.color = color(f32::INFINITY, f32::NAN, 1.0);
+
+        assert!(ray_tree_to_json(&[node]).contains("\"color\":[null,null,1]"));
+    }
+}
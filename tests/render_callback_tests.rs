@@ -0,0 +1,118 @@
+use ray_tracer_challenge::*;
+
+mod common;
+use common::*;
+
+#[cfg(test)]
+mod render_callback {
+    use super::*;
+
+    #[test]
+    /// The default settings render one Whitted sample per pixel in 16 pixel tiles on every core
+    fn default_render_settings() {
+        let s = render_settings();
+
+        assert_eq!(s.samples, 1);
+        assert_eq!(s.max_path_depth, DEFAULT_PATH_DEPTH);
+
+        assert_eq!(s.threads, 0);
+        assert_eq!(s.tile_size, 16);
+        assert_eq!(s.integrator, Integrator::Whitted);
+    }
+
+    #[test]
+    /// Tiles cover the image exactly, with smaller tiles along the edges
+    fn tiles_cover_the_image() {
+        let t = tiles(40, 20, 16);
+
+        assert_eq!(t.len(), 6);
+        assert_eq!(t[0], Tile { x: 0, y: 0, width: 16, height: 16 });
+        assert_eq!(t[2], Tile { x: 32, y: 0, width: 8, height: 16 });
+        assert_eq!(t[5], Tile { x: 32, y: 16, width: 8, height: 4 });
+        assert_eq!(t.iter().map(|t| t.width * t.height).sum::<i32>(), 800);
+    }
+
+    #[test]
+    /// The callback is told about every finished tile
+    fn callback_reports_every_tile() {
+        let w = default_world();
+        let mut s = render_settings();
+        s.tile_size = 4;
+        let mut reports = vec![];
+
+        render_with_callback(&default_camera(), &w, &s, |progress, _| {
+            reports.push(*progress);
+            true
+        });
+
+        assert_eq!(reports.len(), 9);
+        assert!(reports.iter().enumerate().all(|(i, p)| p.completed == i + 1 && p.total == 9));
+    }
+
+    #[test]
+    /// Rendering on several threads gives the same image as on one
+    fn threads_do_not_change_the_image() {
+        let w = default_world();
+        let mut s = render_settings();
+        s.tile_size = 3;
+
+        s.threads = 1;
+        let single = render_with_settings(&default_camera(), &w, &s);
+        s.threads = 4;
+        let multi = render_with_settings(&default_camera(), &w, &s);
+
+        assert_eq!(single.pixel_at(5, 5), color(0.38066, 0.47583, 0.2855));
+        assert_eq!(single.pixels, multi.pixels);
+    }
+
+    #[test]
+    /// Tiles are reported in the same colors as the finished image
+    fn tiles_are_reported_tone_mapped() {
+        let w = default_world();
+        let mut s = render_settings();
+        s.tile_size = 4;
+        s.tone_mapping = ToneMapping { operator: ToneMap::Reinhard, srgb: true, ..tone_mapping() };
+        let mut last = None;
+
+        let image = render_with_callback(&default_camera(), &w, &s, |_, canvas| {
+            last = Some(canvas.clone());
+            true
+        });
+
+        assert_eq!(last.unwrap().pixels, image.pixels);
+    }
+
+    #[test]
+    /// Returning false from the callback cancels the render
+    fn returning_false_cancels_the_render() {
+        let w = default_world();
+        let mut s = render_settings();
+        s.threads = 1;
+        s.tile_size = 1;
+        let mut calls = 0;
+
+        let image = render_with_callback(&default_camera(), &w, &s, |_, _| {
+            calls += 1;
+            false
+        });
+
+        assert_eq!(calls, 1);
+        assert_eq!(image.pixel_at(5, 5), color(0.0, 0.0, 0.0));
+    }
+
+    #[test]
+    /// Settings choose the integrator and the number of samples
+    fn settings_choose_integrator_and_samples() {
+        let w = default_world();
+        let c = default_camera();
+        let mut s = render_settings();
+        s.integrator = Integrator::PathTracer;
+        let single = render_with_callback(&c, &w, &s, |_, _| true);
+        s.samples = 4;
+
+        let image = render_with_callback(&c, &w, &s, |_, _| true);
+
+        assert_eq!(image.pixels, render_with_settings(&c, &w, &s).pixels);
+        assert_ne!(image.pixels, single.pixels);
+    }
+}
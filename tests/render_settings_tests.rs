@@ -0,0 +1,137 @@
+use ray_tracer_challenge::*;
+
+mod common;
+use common::*;
+
+#[cfg(test)]
+mod render_settings {
+    use super::*;
+
+    fn world_with_mirror_floor() -> World {
+        let mut w = default_world();
+        let mut floor = plane();
+        floor.mut_material().reflective = 0.5;
+        floor.set_transform(translation(0.0, -1.0, 0.0));
+        w.objects.push(floor);
+        w
+    }
+
+    fn world_with_glass_slab() -> World {
+        let mut w = world();
+        w.background = Background::Solid(color(0.0, 0.0, 1.0));
+        let mut slab = cube();
+        slab.set_transform(scaling(2.0, 2.0, 0.5));
+        let m = slab.mut_material();
+        m.color = color(0.0, 0.0, 0.0);
+        m.ambient = 0.0;
+        m.transparency = 1.0;
+        m.refractive_index = 1.0;
+        w.objects.push(slab);
+        w
+    }
+
+    fn mirrors_between_glass() -> World {
+        let mut w = world();
+        for (y, reflective, transparency) in [(-1.0, 1.0, 0.0), (1.0, 0.0, 1.0), (2.0, 1.0, 0.0)] {
+            let mut p = plane();
+            p.set_transform(translation(0.0, y, 0.0));
+            let m = p.mut_material();
+            m.color = color(0.0, 0.0, 0.0);
+            m.ambient = 0.0;
+            m.emissive = color(0.1, 0.1, 0.1);
+            m.reflective = reflective;
+            m.transparency = transparency;
+            m.refractive_index = 1.0;
+            w.objects.push(p);
+        }
+        w
+    }
+
+    fn mirror_ray() -> Ray {
+        ray(point(0.0, 0.0, -3.0), vector(0.0, -(2.0f32.sqrt()/2.0), 2.0f32.sqrt()/2.0))
+    }
+
+    #[test]
+    /// The default settings keep the fixed recursion depth and no cutoff
+    fn default_settings() {
+        let s = render_settings();
+
+        assert_eq!(s.max_reflection_depth, DEFAULT_REFLECTION_NUMBER);
+        assert_eq!(s.max_refraction_depth, DEFAULT_REFLECTION_NUMBER);
+        assert_eq!(s.min_contribution, 0.0);
+        assert_eq!(s.ray_budget().contribution, 1.0);
+    }
+
+    #[test]
+    /// Rendering with the default settings matches render()
+    fn rendering_with_default_settings_matches_render() {
+        let w = default_world();
+        let c = default_camera();
+
+        let image = render_with_settings(&c, &w, &render_settings());
+
+        assert_eq!(image.pixel_at(5, 5), color(0.38066, 0.47583, 0.2855));
+        assert_eq!(image.pixels, render(&c, &w).pixels);
+    }
+
+    #[test]
+    /// A reflection depth of zero turns reflections off
+    fn zero_reflection_depth_turns_reflections_off() {
+        let w = world_with_mirror_floor();
+        let mut s = render_settings();
+
+        let reflected = color_at_within(&w, mirror_ray(), s.ray_budget());
+        s.max_reflection_depth = 0;
+        let plain = color_at_within(&w, mirror_ray(), s.ray_budget());
+
+        assert_eq!(reflected, color(0.87677, 0.92436, 0.82918));
+        assert_eq!(plain, color(0.68645, 0.68646, 0.68644));
+    }
+
+    #[test]
+    /// Refraction depth is limited independently of reflection depth
+    fn refraction_depth_is_independent_of_reflection_depth() {
+        let w = world_with_glass_slab();
+        let r = ray(point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0));
+        let mut s = render_settings();
+
+        s.max_reflection_depth = 0;
+        let through = color_at_within(&w, r, s.ray_budget());
+        s.max_reflection_depth = DEFAULT_REFLECTION_NUMBER;
+        s.max_refraction_depth = 1;
+        let stopped = color_at_within(&w, r, s.ray_budget());
+
+        assert_eq!(through, color(0.0, 0.0, 1.0));
+        assert_eq!(stopped, color(0.0, 0.0, 0.0));
+    }
+
+    #[test]
+    /// Rays contributing less than the cutoff are not traced
+    fn rays_below_contribution_cutoff_are_not_traced() {
+        let w = world_with_mirror_floor();
+        let mut s = render_settings();
+
+        s.min_contribution = 0.4;
+        let traced = color_at_within(&w, mirror_ray(), s.ray_budget());
+        s.min_contribution = 0.6;
+        let cut = color_at_within(&w, mirror_ray(), s.ray_budget());
+
+        assert_eq!(traced, color(0.87677, 0.92436, 0.82918));
+        assert_eq!(cut, color(0.68645, 0.68646, 0.68644));
+    }
+
+    #[test]
+    /// The remaining count of the u8 entry points is shared by reflections and refractions
+    fn remaining_depth_is_shared_by_reflections_and_refractions() {
+        let w = mirrors_between_glass();
+        let r = ray(point(0.0, 0.0, 0.0), vector(0.0, -1.0, 0.0));
+
+        assert_eq!(color_at(&w, r, 0), color(0.1, 0.1, 0.1));
+        assert_eq!(color_at(&w, r, 4), color(0.5, 0.5, 0.5));
+
+        let mut s = render_settings();
+        s.max_reflection_depth = 4;
+        s.max_refraction_depth = 4;
+        assert_eq!(color_at_within(&w, r, s.ray_budget()), color(0.9, 0.9, 0.9));
+    }
+}
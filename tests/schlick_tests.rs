@@ -0,0 +1,112 @@
+use std::ops::{Deref, DerefMut};
+use ray_tracer_challenge::*;
+
+#[cfg(test)]
+mod fresnel {
+    use super::*;
+
+    fn assert_close(actual: f32, expected: f32) {
+        assert!((actual - expected).abs() < EPS, "{actual} != {expected}");
+    }
+
+    #[test]
+    /// The Schlick approximation leaving glass at 30°
+    fn schlick_leaving_glass_at_30_degrees() {
+        let shape = glass_sphere();
+        let r = ray(point(0.0, 0.5, 0.0), vector(0.0, 0.0, 1.0));
+        let xs = [intersection(-(0.75f32.sqrt()), shape.deref()), intersection(0.75f32.sqrt(), shape.deref())].to_vec();
+
+        let comps = prepare_computations(xs[1], r, &xs);
+
+        assert_eq!(comps.n1, 1.5);
+        assert_eq!(comps.n2, 1.0);
+        assert_close(schlick(&comps), 0.04427);
+    }
+
+    #[test]
+    /// The exact Fresnel reflectance leaving glass at 30°
+    fn exact_fresnel_leaving_glass_at_30_degrees() {
+        let shape = glass_sphere();
+        let r = ray(point(0.0, 0.5, 0.0), vector(0.0, 0.0, 1.0));
+        let xs = [intersection(-(0.75f32.sqrt()), shape.deref()), intersection(0.75f32.sqrt(), shape.deref())].to_vec();
+
+        let comps = prepare_computations(xs[1], r, &xs);
+
+        assert_close(fresnel_exact(&comps), 0.05519);
+    }
+
+    #[test]
+    /// The exact Fresnel reflectance with a perpendicular viewing angle
+    fn exact_fresnel_with_a_perpendicular_viewing_angle() {
+        let shape = glass_sphere();
+        let r = ray(point(0.0, 0.0, 0.0), vector(0.0, 1.0, 0.0));
+        let xs = [intersection(-1.0, shape.deref()), intersection(1.0, shape.deref())].to_vec();
+
+        let comps = prepare_computations(xs[1], r, &xs);
+
+        assert_close(fresnel_exact(&comps), 0.04);
+    }
+
+    #[test]
+    /// The exact Fresnel reflectance under total internal reflection
+    fn exact_fresnel_under_total_internal_reflection() {
+        let shape = glass_sphere();
+        let r = ray(point(0.0, 0.0, 2.0f32.sqrt() / 2.0), vector(0.0, 1.0, 0.0));
+        let xs = [intersection(-2.0f32.sqrt() / 2.0, shape.deref()), intersection(2.0f32.sqrt() / 2.0, shape.deref())].to_vec();
+
+        let comps = prepare_computations(xs[1], r, &xs);
+
+        assert_close(fresnel_exact(&comps), 1.0);
+    }
+
+    #[test]
+    /// The exact Fresnel reflectance entering glass at 60°
+    fn exact_fresnel_entering_glass_at_60_degrees() {
+        let shape = glass_sphere();
+        let r = ray(point(0.0, 0.75f32.sqrt(), -2.0), vector(0.0, 0.0, 1.0));
+        let xs = [intersection(1.5, shape.deref())].to_vec();
+
+        let comps = prepare_computations(xs[0], r, &xs);
+
+        assert_close(fresnel_exact(&comps), 0.08919);
+    }
+
+    #[test]
+    /// Materials use the Schlick approximation by default
+    fn materials_use_schlick_by_default() {
+        let mut shape = glass_sphere();
+        assert_eq!(shape.material().fresnel, Fresnel::Schlick);
+
+        let r = ray(point(0.0, 0.5, 0.0), vector(0.0, 0.0, 1.0));
+        let xs = [intersection(-(0.75f32.sqrt()), shape.deref()), intersection(0.75f32.sqrt(), shape.deref())].to_vec();
+        let comps = prepare_computations(xs[1], r, &xs);
+        assert_eq!(fresnel(&comps), schlick(&comps));
+
+        shape.deref_mut().mut_material().fresnel = Fresnel::Exact;
+        let xs = [intersection(-(0.75f32.sqrt()), shape.deref()), intersection(0.75f32.sqrt(), shape.deref())].to_vec();
+        let comps = prepare_computations(xs[1], r, &xs);
+        assert_eq!(fresnel(&comps), fresnel_exact(&comps));
+    }
+
+    #[test]
+    /// A transparent surface reflects by Fresnel in shade_hit without being marked reflective
+    fn transparent_surface_reflects_without_being_reflective() {
+        let mut w = world();
+        w.background = Background::Solid(color(1.0, 1.0, 1.0));
+        let mut floor = plane();
+        floor.mut_material().color = color(0.0, 0.0, 0.0);
+        floor.mut_material().specular = 0.0;
+        floor.mut_material().transparency = 1.0;
+        floor.mut_material().refractive_index = 1.5;
+        w.objects.push(floor);
+
+        let r = ray(point(0.0, 1.0, -1.0), normalize(vector(0.0, -1.0, 1.0)));
+        let xs = [intersection(2.0f32.sqrt(), w.objects[0].deref())].to_vec();
+        let comps = prepare_computations(xs[0], r, &xs);
+
+        let reflectance = schlick(&comps);
+        assert!(reflectance > 0.04);
+        assert_eq!(reflected_color(&w, &comps, DEFAULT_REFLECTION_NUMBER), color(0.0, 0.0, 0.0));
+        assert_eq!(shade_hit(&w, &comps, DEFAULT_REFLECTION_NUMBER), color(1.0, 1.0, 1.0));
+    }
+}
@@ -0,0 +1,126 @@
+use ray_tracer_challenge::*;
+
+#[cfg(test)]
+mod shadow {
+    use super::*;
+
+    fn world_with_blocker(transparency: f32) -> World {
+        let mut w = world();
+        w.lights.push(point_light(point(0.0, 10.0, 0.0), color(1.0, 1.0, 1.0)));
+        let mut blocker = sphere();
+        blocker.set_transform(translation(0.0, 5.0, 0.0));
+        blocker.mut_material().transparency = transparency;
+        w.objects.push(blocker);
+        w
+    }
+
+    #[test]
+    /// Shapes cast shadows by default
+    fn shapes_cast_shadows_by_default() {
+        assert!(sphere().casts_shadow());
+        assert!(plane().casts_shadow());
+        assert!(cube().casts_shadow());
+        assert!(group().casts_shadow());
+    }
+
+    #[test]
+    /// An opaque object blocks all light
+    fn opaque_object_blocks_all_light() {
+        let w = world_with_blocker(0.0);
+
+        let a = shadow_attenuation(&w, point(0.0, 0.0, 0.0), vector(0.0, 1.0, 0.0), 10.0, 0.0);
+
+        assert_eq!(a, color(0.0, 0.0, 0.0));
+        assert!(is_shadowed(&w, point(0.0, 0.0, 0.0)));
+    }
+
+    #[test]
+    /// Light is filtered once by each transparent object it crosses
+    fn transparent_object_filters_light() {
+        let w = world_with_blocker(0.5);
+
+        let a = shadow_attenuation(&w, point(0.0, 0.0, 0.0), vector(0.0, 1.0, 0.0), 10.0, 0.0);
+
+        assert_eq!(a, color(0.5, 0.5, 0.5));
+        assert!(!is_shadowed(&w, point(0.0, 0.0, 0.0)));
+    }
+
+    #[test]
+    /// Colored glass tints the light passing through it
+    fn colored_glass_tints_the_light() {
+        let mut w = world_with_blocker(0.5);
+        w.objects[0].mut_material().color = color(1.0, 0.5, 0.0);
+
+        let a = shadow_attenuation(&w, point(0.0, 0.0, 0.0), vector(0.0, 1.0, 0.0), 10.0, 0.0);
+
+        assert_eq!(a, color(0.5, 0.25, 0.0));
+    }
+
+    #[test]
+    /// Absorbing glass loses light with the distance travelled inside it
+    fn absorbing_glass_attenuates_by_distance() {
+        let mut w = world_with_blocker(1.0);
+        w.objects[0].mut_material().absorption = color(0.5, 0.5, 0.5);
+        w.objects[0].mut_material().absorption_density = 1.0;
+
+        let a = shadow_attenuation(&w, point(0.0, 0.0, 0.0), vector(0.0, 1.0, 0.0), 10.0, 0.0);
+
+        assert_eq!(a, color(0.25, 0.25, 0.25));
+    }
+
+    #[test]
+    /// Surfaces beyond the light do not filter it
+    fn surfaces_beyond_the_light_do_not_filter_it() {
+        let w = world_with_blocker(0.5);
+
+        let a = shadow_attenuation(&w, point(0.0, 0.0, 0.0), vector(0.0, 1.0, 0.0), 4.5, 0.0);
+
+        assert_eq!(a, color(0.5, 0.5, 0.5));
+    }
+
+    #[test]
+    /// An object flagged not to cast shadows lets all light through
+    fn object_not_casting_shadows_lets_light_through() {
+        let mut w = world_with_blocker(0.0);
+        w.objects[0].set_casts_shadow(false);
+
+        let a = shadow_attenuation(&w, point(0.0, 0.0, 0.0), vector(0.0, 1.0, 0.0), 10.0, 0.0);
+
+        assert_eq!(a, color(1.0, 1.0, 1.0));
+        assert!(!is_shadowed(&w, point(0.0, 0.0, 0.0)));
+    }
+
+    #[test]
+    /// Lighting scales diffuse and specular light by its visibility
+    fn lightning_scales_direct_light_by_visibility() {
+        let m = material();
+        let s = sphere();
+        let position = point(0.0, 0.0, 0.0);
+        let eye_v = vector(0.0, 0.0, -1.0);
+        let normal_v = vector(0.0, 0.0, -1.0);
+        let light = point_light(point(0.0, 0.0, -10.0), color(1.0, 1.0, 1.0));
+
+        let result = lightning_filtered(&m, s.as_ref(), &light, position, eye_v, normal_v, color(0.5, 0.5, 0.5));
+
+        assert_eq!(result, color(1.0, 1.0, 1.0));
+    }
+
+    #[test]
+    /// A glass sphere casts a lighter shadow than an opaque one
+    fn glass_casts_a_lighter_shadow() {
+        let mut opaque = world_with_blocker(0.0);
+        let mut glass = world_with_blocker(0.9);
+        for w in [&mut opaque, &mut glass] {
+            let mut floor = plane();
+            floor.mut_material().specular = 0.0;
+            w.objects.push(floor);
+        }
+        let r = ray(point(0.0, 1.0, -1.0), vector(0.0, -1.0, 1.0));
+
+        let dark = color_at(&opaque, r, DEFAULT_REFLECTION_NUMBER);
+        let light = color_at(&glass, r, DEFAULT_REFLECTION_NUMBER);
+
+        assert_eq!(dark, color(0.1, 0.1, 0.1));
+        assert_eq!(light, color(0.91, 0.91, 0.91));
+    }
+}
@@ -0,0 +1,87 @@
+use ray_tracer_challenge::*;
+
+mod common;
+use common::*;
+
+#[cfg(test)]
+mod tone_mapping {
+    use super::*;
+
+    fn canvas_of(c: Color) -> Canvas {
+        let mut canvas = Canvas::new(1, 1);
+        canvas.write_pixel(0, 0, c);
+        canvas
+    }
+
+    #[test]
+    /// The default tone mapping leaves linear colors untouched
+    fn default_tone_mapping_is_the_identity() {
+        let t = tone_mapping();
+
+        assert_eq!(t.exposure, 0.0);
+        assert_eq!(t.operator, ToneMap::Linear);
+        assert!(!t.srgb);
+        assert_eq!(tone_map(&canvas_of(color(1.5, 0.25, -0.5)), &t).pixel_at(0, 0), color(1.5, 0.25, -0.5));
+    }
+
+    #[test]
+    /// Every stop of exposure doubles the brightness
+    fn exposure_is_measured_in_stops() {
+        let mut t = tone_mapping();
+        t.exposure = 1.0;
+
+        assert_eq!(tone_map(&canvas_of(color(0.1, 0.2, 0.3)), &t).pixel_at(0, 0), color(0.2, 0.4, 0.6));
+        t.exposure = -2.0;
+        assert_eq!(tone_map(&canvas_of(color(0.4, 0.8, 1.2)), &t).pixel_at(0, 0), color(0.1, 0.2, 0.3));
+    }
+
+    #[test]
+    /// Reinhard compresses highlights without clipping them
+    fn reinhard_compresses_highlights() {
+        assert_eq!(reinhard(0.0), 0.0);
+        assert_eq!(reinhard(1.0), 0.5);
+        assert_eq!(reinhard(4.0), 0.8);
+        assert!(reinhard(1000.0) < 1.0);
+    }
+
+    #[test]
+    /// ACES follows the filmic curve and saturates at white
+    fn aces_follows_the_filmic_curve() {
+        assert_eq!(aces(0.0), 0.0);
+        assert!((aces(1.0) - 0.80380).abs() < EPS);
+        assert_eq!(aces(100.0), 1.0);
+    }
+
+    #[test]
+    /// sRGB encoding brightens midtones and keeps black and white
+    fn srgb_encoding_brightens_midtones() {
+        assert_eq!(srgb_encode(0.0), 0.0);
+        assert!((srgb_encode(0.002) - 0.02584).abs() < EPS);
+        assert!((srgb_encode(0.5) - 0.73536).abs() < EPS);
+        assert!((srgb_encode(1.0) - 1.0).abs() < EPS);
+        assert_eq!(srgb_encode(2.0), srgb_encode(1.0));
+    }
+
+    #[test]
+    /// Operators are applied before the sRGB encoding
+    fn operator_is_applied_before_encoding() {
+        let t = ToneMapping { exposure: 1.0, operator: ToneMap::Reinhard, srgb: true };
+
+        let c = tone_map(&canvas_of(color(0.5, 0.5, 0.5)), &t).pixel_at(0, 0);
+
+        assert!((c.red - 0.73536).abs() < EPS);
+    }
+
+    #[test]
+    /// Render settings apply their tone mapping to the finished image
+    fn render_settings_apply_tone_mapping() {
+        let w = default_world();
+        let c = default_camera();
+        let mut s = render_settings();
+        s.tone_mapping.operator = ToneMap::Reinhard;
+
+        let image = render_with_settings(&c, &w, &s);
+
+        assert_eq!(image.pixel_at(5, 5), color(reinhard(0.38066), reinhard(0.47583), reinhard(0.2855)));
+    }
+}
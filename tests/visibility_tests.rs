@@ -0,0 +1,128 @@
+use ray_tracer_challenge::*;
+
+#[cfg(test)]
+mod visibility {
+    use super::*;
+
+    fn hidden_from(kind: RayKind) -> Visibility {
+        let mut v = visibility();
+        match kind {
+            | RayKind::Camera => v.camera = false,
+            | RayKind::Shadow => v.shadow = false,
+            | RayKind::Reflection => v.reflection = false,
+            | RayKind::Refraction => v.refraction = false,
+        }
+        v
+    }
+
+    #[test]
+    /// Primary rays are camera rays and keep their kind when transformed
+    fn primary_rays_are_camera_rays() {
+        let r = ray(point(1.0, 2.0, 3.0), vector(0.0, 1.0, 0.0));
+        let reflected = ray_of_kind(point(1.0, 2.0, 3.0), vector(0.0, 1.0, 0.0), 0.0, RayKind::Reflection);
+
+        assert_eq!(r.kind, RayKind::Camera);
+        assert_eq!(ray_for_pixel(&camera(11, 11, 1.0), 5, 5).kind, RayKind::Camera);
+        assert_eq!(transform(reflected, translation(3.0, 4.0, 5.0)).kind, RayKind::Reflection);
+    }
+
+    #[test]
+    /// Shapes are visible to every kind of ray by default
+    fn shapes_are_visible_to_every_ray_by_default() {
+        let s = sphere();
+
+        for kind in [RayKind::Camera, RayKind::Shadow, RayKind::Reflection, RayKind::Refraction] {
+            assert!(s.visibility().sees(kind));
+        }
+    }
+
+    #[test]
+    /// Intersecting a world skips shapes hidden from the ray's kind
+    fn intersect_world_skips_hidden_shapes() {
+        let mut w = default_world();
+        w.objects[0].set_visibility(hidden_from(RayKind::Reflection));
+        let origin = point(0.0, 0.0, -5.0);
+        let direction = vector(0.0, 0.0, 1.0);
+
+        let camera_xs = intersect_world(&w, ray(origin, direction));
+        let reflection_xs = intersect_world(&w, ray_of_kind(origin, direction, 0.0, RayKind::Reflection));
+
+        assert_eq!(camera_xs.len(), 4);
+        assert_eq!(reflection_xs.len(), 2);
+        assert_eq!(reflection_xs[0].t, 4.5);
+    }
+
+    #[test]
+    /// A shape hidden from the camera still shows up in a mirror
+    fn shape_hidden_from_camera_shows_in_mirror() {
+        let mut w = world();
+        w.lights.push(point_light(point(0.0, 10.0, -10.0), color(1.0, 1.0, 1.0)));
+        let mut mirror = plane();
+        mirror.set_transform(translation(0.0, -1.0, 0.0));
+        let m = mirror.mut_material();
+        m.color = color(0.0, 0.0, 0.0);
+        m.ambient = 0.0;
+        m.diffuse = 0.0;
+        m.specular = 0.0;
+        m.reflective = 1.0;
+        w.objects.push(mirror);
+        let mut helper = sphere();
+        helper.set_transform(translation(0.0, 1.0, 0.0));
+        helper.mut_material().ambient = 1.0;
+        helper.set_visibility(hidden_from(RayKind::Camera));
+        w.objects.push(helper);
+
+        let direct = color_at(&w, ray(point(0.0, 1.0, -5.0), vector(0.0, 0.0, 1.0)), DEFAULT_REFLECTION_NUMBER);
+        let reflected = color_at(&w, ray(point(0.0, 1.0, -5.0), normalize(vector(0.0, -4.0, 5.0))), DEFAULT_REFLECTION_NUMBER);
+
+        assert_eq!(direct, color(0.0, 0.0, 0.0));
+        assert!(reflected.red > 1.0);
+    }
+
+    #[test]
+    /// A shape hidden from refraction rays disappears behind glass
+    fn shape_hidden_from_refraction_disappears_behind_glass() {
+        let mut w = world();
+        w.background = Background::Solid(color(0.0, 0.0, 1.0));
+        let mut glass = cube();
+        glass.set_transform(scaling(2.0, 2.0, 0.1));
+        let m = glass.mut_material();
+        m.ambient = 0.0;
+        m.diffuse = 0.0;
+        m.specular = 0.0;
+        m.transparency = 1.0;
+        m.refractive_index = 1.0;
+        w.objects.push(glass);
+        let mut behind = sphere();
+        behind.set_transform(translation(0.0, 0.0, 5.0));
+        behind.mut_material().ambient = 1.0;
+        behind.set_visibility(hidden_from(RayKind::Refraction));
+        w.objects.push(behind);
+
+        let c = color_at(&w, ray(point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0)), DEFAULT_REFLECTION_NUMBER);
+
+        assert_eq!(c, color(0.0, 0.0, 1.0));
+    }
+
+    #[test]
+    /// casts_shadow reads and writes the shadow visibility flag
+    fn casts_shadow_follows_shadow_visibility() {
+        let mut s = sphere();
+
+        s.set_casts_shadow(false);
+
+        assert!(!s.casts_shadow());
+        assert_eq!(s.visibility(), hidden_from(RayKind::Shadow));
+    }
+
+    #[test]
+    /// Shapes that do not cast shadows are skipped by shadow rays
+    fn shapes_without_shadows_are_skipped_by_shadow_rays() {
+        let mut w = default_world();
+        for o in w.objects.iter_mut() {
+            o.set_casts_shadow(false);
+        }
+
+        assert!(!is_shadowed(&w, point(10.0, -10.0, 10.0)));
+    }
+}
@@ -0,0 +1,146 @@
+use ray_tracer_challenge::*;
+
+#[cfg(test)]
+mod fog {
+    use super::*;
+
+    #[test]
+    /// Worlds have no fog by default
+    fn worlds_have_no_fog_by_default() {
+        let w = default_world();
+
+        assert!(w.fog.is_none());
+        assert_eq!(fog_transmittance(&w, 100.0), 1.0);
+    }
+
+    #[test]
+    /// Fog transmittance falls off exponentially with distance
+    fn fog_transmittance_falls_off_exponentially() {
+        let mut w = world();
+        w.fog = Some(fog(color(1.0, 1.0, 1.0), 0.5));
+
+        assert_eq!(fog_transmittance(&w, 0.0), 1.0);
+        assert!((fog_transmittance(&w, 2.0) - (-1.0f32).exp()).abs() < EPS);
+    }
+
+    #[test]
+    /// A ray that misses everything sees only fog
+    fn missed_ray_is_fully_fogged() {
+        let mut w = world();
+        w.background = Background::Solid(color(0.0, 0.0, 1.0));
+        w.fog = Some(fog(color(0.5, 0.5, 0.5), 0.1));
+        let r = ray(point(0.0, 0.0, -5.0), vector(0.0, 1.0, 0.0));
+
+        assert_eq!(color_at(&w, r, DEFAULT_REFLECTION_NUMBER), color(0.5, 0.5, 0.5));
+    }
+
+    #[test]
+    /// Fog blends a hit with the fog color by distance
+    fn fog_blends_hit_with_fog_color() {
+        let mut w = default_world();
+        w.fog = Some(fog(color(1.0, 1.0, 1.0), 0.1));
+        let r = ray(point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0));
+        let t = (-0.4f32).exp();
+
+        let c = color_at(&w, r, DEFAULT_REFLECTION_NUMBER);
+
+        assert_eq!(c, color(0.38066, 0.47583, 0.2855) * t + color(1.0, 1.0, 1.0) * (1.0 - t));
+    }
+}
+
+#[cfg(test)]
+mod constant_medium {
+    use super::*;
+
+    #[test]
+    /// A constant medium is a volume, ordinary shapes are not
+    fn constant_medium_is_a_volume() {
+        let medium = constant_medium(sphere(), 1.0);
+
+        assert!(medium.is_volume());
+        assert!(!sphere().is_volume());
+    }
+
+    #[test]
+    /// A constant medium follows the transform of its boundary
+    fn constant_medium_follows_boundary_transform() {
+        let mut medium = constant_medium(sphere(), 1.0e6);
+        medium.set_transform(translation(0.0, 0.0, 10.0));
+        let r = ray(point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0));
+
+        let xs = medium.intersect(r);
+
+        assert_eq!(medium.transform(), translation(0.0, 0.0, 10.0));
+        assert_eq!(xs.len(), 2);
+        assert_eq!(xs[0].t, 14.0);
+        assert_eq!(xs[1].t, 16.0);
+    }
+
+    #[test]
+    /// A ray missing the boundary never enters the medium
+    fn ray_missing_boundary_never_enters() {
+        let medium = constant_medium(sphere(), 1.0e6);
+        let r = ray(point(0.0, 2.0, -5.0), vector(0.0, 0.0, 1.0));
+
+        assert!(medium.intersect(r).is_empty());
+    }
+
+    #[test]
+    /// Intersecting a medium always returns its boundary, however dense it is
+    fn intersecting_a_medium_is_deterministic() {
+        let medium = constant_medium(sphere(), 1.0);
+        let r = ray(point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0));
+
+        for _ in 0..10 {
+            let xs = medium.intersect(r);
+            assert_eq!(xs.iter().map(|i| i.t).collect::<Vec<_>>(), vec![4.0, 6.0]);
+        }
+    }
+
+    #[test]
+    /// A dense medium scatters the ray, a thin one lets it through to the background
+    fn density_controls_whether_the_ray_scatters() {
+        let r = ray(point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0));
+        let mut w = world();
+        w.background = Background::Solid(color(0.0, 0.0, 1.0));
+        w.lights.push(point_light(point(0.0, 0.0, -10.0), color(1.0, 1.0, 1.0)));
+
+        w.objects = vec![constant_medium(sphere(), 1.0e6)];
+        assert_eq!(color_at(&w, r, DEFAULT_REFLECTION_NUMBER), color(1.0, 1.0, 1.0));
+
+        w.objects = vec![constant_medium(sphere(), 1.0e-6)];
+        assert_eq!(color_at(&w, r, DEFAULT_REFLECTION_NUMBER), color(0.0, 0.0, 1.0));
+    }
+
+    #[test]
+    /// Shadow rays lose light exponentially with the distance travelled through a medium
+    fn shadow_rays_are_attenuated_by_beer_lambert() {
+        let mut w = world();
+        w.objects.push(constant_medium(sphere(), 0.5));
+        let t = (-1.0f32).exp();
+
+        let through = shadow_attenuation(&w, point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0), 10.0, 0.0);
+        let from_inside = shadow_attenuation(&w, point(0.0, 0.0, 0.0), vector(0.0, 0.0, 1.0), 10.0, 0.0);
+
+        assert_eq!(through, color(t, t, t));
+        assert_eq!(from_inside, color(t.sqrt(), t.sqrt(), t.sqrt()));
+    }
+
+    #[test]
+    /// A dense medium scatters light that reaches it and shadows light that has to cross it
+    fn dense_medium_scatters_and_shadows_light() {
+        let mut medium = constant_medium(sphere(), 1.0e6);
+        medium.mut_material().color = color(0.5, 0.5, 0.5);
+        let mut w = world();
+        w.objects.push(medium);
+        let r = ray(point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0));
+
+        w.lights = vec![point_light(point(0.0, 0.0, -10.0), color(1.0, 1.0, 1.0))];
+        let lit = color_at(&w, r, DEFAULT_REFLECTION_NUMBER);
+        w.lights = vec![point_light(point(0.0, 0.0, 10.0), color(1.0, 1.0, 1.0))];
+        let shadowed = color_at(&w, r, DEFAULT_REFLECTION_NUMBER);
+
+        assert_eq!(lit, color(0.5, 0.5, 0.5));
+        assert_eq!(shadowed, color(0.05, 0.05, 0.05));
+    }
+}
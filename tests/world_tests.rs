@@ -267,7 +267,7 @@ mod world {
     }
 
     #[test]
-    /// shade_hit() with a transparent material p. 159
+    /// shade_hit() with a transparent material p. 159, reflecting by Fresnel and lit through the floor
     fn shade_hit_with_a_transparent_material() {
         let mut w = default_world();
         let mut floor = plane();
@@ -290,11 +290,11 @@ mod world {
         let comps = prepare_computations(xs[0], r, &xs);
 
         let c = shade_hit(&w, &comps, 5);
-        assert_eq!(c, color(0.93642, 0.68642, 0.68642))
+        assert_eq!(c, color(1.12301, 0.70644, 0.69843))
     }
 
     #[test]
-    /// shade_hit() with a reflective, transparent material p. 164
+    /// shade_hit() with a reflective, transparent material p. 164, lit through the floor
     fn shade_hit_with_a_reflective_and_transparent_material() {
         let mut w = default_world();
         let mut floor = plane();
@@ -317,6 +317,6 @@ mod world {
         let comps = prepare_computations(xs[0], r, &xs);
 
         let c = shade_hit(&w, &comps, 5);
-        assert_eq!(c, color(0.93391, 0.69643, 0.69243))
+        assert_eq!(c, color(1.09506, 0.93437, 0.83519))
     }
 }
\ No newline at end of file